@@ -1,8 +1,11 @@
 use eframe::{
-    egui::{Grid, Layout, ScrollArea},
+    egui::{Grid, Layout, ScrollArea, Ui},
     emath::Align,
 };
-use lib::Span;
+use lib::{format_duration, Span};
+
+/// Number of attributes listed in [`tooltip`].
+const TOOLTIP_ATTRIBUTES: usize = 8;
 
 pub(crate) struct Attributes {
     span: Span,
@@ -20,6 +23,27 @@ impl crate::Panel for Attributes {
         ui.separator();
 
         ScrollArea::vertical().show(ui, |ui| {
+            Grid::new("span_timing").num_columns(2).show(ui, |ui| {
+                ui.label("duration:");
+                ui.with_layout(Layout::right_to_left(Align::TOP), |ui| {
+                    ui.label(format_duration(self.span.duration_nanos));
+                });
+                ui.end_row();
+
+                ui.label("self time:");
+                ui.with_layout(Layout::right_to_left(Align::TOP), |ui| {
+                    ui.label(format_duration(self.span.self_nanos));
+                });
+                ui.end_row();
+
+                ui.label("offset:");
+                ui.with_layout(Layout::right_to_left(Align::TOP), |ui| {
+                    ui.label(format_duration(self.span.offset_nanos));
+                });
+                ui.end_row();
+            });
+            ui.add_space(10.0);
+
             if !self.span.attributes.is_empty() {
                 ui.heading("Attributes");
                 Grid::new("span_attributes").num_columns(2).show(ui, |ui| {
@@ -50,3 +74,50 @@ impl crate::Panel for Attributes {
         None
     }
 }
+
+/// Summary of `span` for hover tooltips: timing, status and the first
+/// few attributes.
+pub(crate) fn tooltip(ui: &mut Ui, span: &Span) {
+    ui.strong(&span.name);
+    Grid::new("span_tooltip").num_columns(2).show(ui, |ui| {
+        let mut row = |key: &str, value: String| {
+            ui.weak(key);
+            ui.label(value);
+            ui.end_row();
+        };
+        row("service", span.service_name().unwrap_or("-").to_string());
+        row("start", format!("+{}", format_duration(span.offset_nanos)));
+        row("duration", format_duration(span.duration_nanos));
+        row("self time", format_duration(span.self_nanos));
+        let status = match (
+            span.metadata.get("status.code"),
+            span.metadata.get("status.description"),
+        ) {
+            (Some(code), Some(description)) if !description.is_empty() => {
+                format!("{code}: {description}")
+            }
+            (Some(code), _) => code.clone(),
+            (None, _) => "-".to_string(),
+        };
+        row("status", status);
+    });
+
+    if !span.attributes.is_empty() {
+        ui.separator();
+        Grid::new("span_tooltip_attributes")
+            .num_columns(2)
+            .show(ui, |ui| {
+                for (key, value) in span.attributes.iter().take(TOOLTIP_ATTRIBUTES) {
+                    ui.weak(key);
+                    ui.label(if value.is_empty() { "-" } else { value });
+                    ui.end_row();
+                }
+            });
+        if span.attributes.len() > TOOLTIP_ATTRIBUTES {
+            ui.weak(format!(
+                "+{} more, click the span name to see all",
+                span.attributes.len() - TOOLTIP_ATTRIBUTES
+            ));
+        }
+    }
+}
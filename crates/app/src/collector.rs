@@ -8,7 +8,7 @@ use lib::Trace;
 use tokio::{sync::mpsc, task::JoinHandle};
 use tracing::error;
 
-use crate::Panel;
+use crate::{live::Live, Panel, StoreWriter};
 use lib::collector::run;
 
 #[derive(Debug, Default)]
@@ -22,6 +22,10 @@ pub(crate) struct Collector {
     /// Traces owned by [`App`]. Rebuilt when collector server ingests
     /// a new batch of spans.
     traces: Arc<Mutex<Vec<Trace>>>,
+    /// Holds batches back while the trace list is paused.
+    live: Arc<Mutex<Live>>,
+    /// Saves batches as they arrive.
+    writer: Option<StoreWriter>,
 }
 
 impl Panel for Collector {
@@ -66,13 +70,19 @@ impl Panel for Collector {
 }
 
 impl Collector {
-    pub(crate) fn new(traces: Arc<Mutex<Vec<Trace>>>) -> Self {
+    pub(crate) fn new(
+        traces: Arc<Mutex<Vec<Trace>>>,
+        live: Arc<Mutex<Live>>,
+        writer: Option<StoreWriter>,
+    ) -> Self {
         Self {
             refresh_duration: Duration::from_millis(250),
             host: "localhost".into(),
             port: "3000".into(),
             task: None,
             traces,
+            live,
+            writer,
         }
     }
 
@@ -107,8 +117,10 @@ impl Collector {
         self.task = Some(tokio::spawn(async move { run(tx, addr).await }));
 
         let traces = self.traces.clone();
+        let live = self.live.clone();
+        let writer = self.writer.clone();
         tokio::spawn(async move {
-            crate::collect_spans_and_recalculate(rx, traces).await;
+            crate::collect_spans_and_recalculate(rx, traces, live, writer).await;
         });
         Ok(())
     }
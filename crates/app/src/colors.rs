@@ -0,0 +1,102 @@
+use eframe::egui::Color32;
+use lib::{Span, SpanKind};
+
+pub(crate) const DARTMOUTH_GREEN: Color32 = Color32::from_rgb(0x0B, 0x6E, 0x4F);
+pub(crate) const INDIAN_RED: Color32 = Color32::from_rgb(0xF2, 0x54, 0x5B);
+pub(crate) const ULTRA_VIOLET: Color32 = Color32::from_rgb(0x64, 0x5E, 0x9D);
+pub(crate) const ROBIN_EGG_BLUE: Color32 = Color32::from_rgb(0x2D, 0xC2, 0xBD);
+pub(crate) const SAFFRON: Color32 = Color32::from_rgb(0xE9, 0xB8, 0x2E);
+pub(crate) const PERSIMMON: Color32 = Color32::from_rgb(0xE0, 0x6C, 0x1F);
+pub(crate) const CERULEAN: Color32 = Color32::from_rgb(0x1F, 0x78, 0xB4);
+pub(crate) const OLIVINE: Color32 = Color32::from_rgb(0x8A, 0xA0, 0x3B);
+pub(crate) const ORCHID: Color32 = Color32::from_rgb(0xC2, 0x6D, 0xBC);
+pub(crate) const SLATE: Color32 = Color32::from_rgb(0x6C, 0x7A, 0x89);
+
+/// Colors handed out to values by [`hashed`]. Red is left out so it
+/// only ever means an error.
+pub(crate) const PALETTE: [Color32; 8] = [
+    DARTMOUTH_GREEN,
+    ULTRA_VIOLET,
+    ROBIN_EGG_BLUE,
+    SAFFRON,
+    PERSIMMON,
+    CERULEAN,
+    OLIVINE,
+    ORCHID,
+];
+
+/// Palette color picked by a hash of `value`, so the same value (for
+/// example a service) gets the same color in every tab and session.
+pub(crate) fn hashed(value: &str) -> Color32 {
+    // FNV-1a; std's hasher is randomly seeded per process
+    let hash = value.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    });
+    #[allow(clippy::cast_possible_truncation)]
+    let idx = (hash % PALETTE.len() as u64) as usize;
+    PALETTE[idx]
+}
+
+/// What decides the color of a span's bar.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) enum ColorBy {
+    #[default]
+    Service,
+    Name,
+    /// Errors red, everything else by whether status was set.
+    Status,
+    Kind,
+    /// Hash of the value of the attribute with this key.
+    Attribute(String),
+}
+
+impl ColorBy {
+    pub(crate) fn label(&self) -> String {
+        match self {
+            Self::Service => "Service".to_string(),
+            Self::Name => "Name".to_string(),
+            Self::Status => "Status".to_string(),
+            Self::Kind => "Kind".to_string(),
+            Self::Attribute(key) => format!("Attribute: {key}"),
+        }
+    }
+
+    /// Legend entry `span` falls under.
+    pub(crate) fn key(&self, span: &Span) -> String {
+        match self {
+            Self::Service => span.service_name().unwrap_or("-").to_string(),
+            Self::Name => span.name.clone(),
+            Self::Status => span
+                .metadata
+                .get("status.code")
+                .cloned()
+                .unwrap_or_else(|| "-".to_string()),
+            Self::Kind => format!("{:?}", span.kind),
+            Self::Attribute(key) => span
+                .attributes
+                .get(key)
+                .cloned()
+                .unwrap_or_else(|| "-".to_string()),
+        }
+    }
+
+    pub(crate) fn color(&self, span: &Span) -> Color32 {
+        match self {
+            Self::Status if span.is_error() => INDIAN_RED,
+            Self::Status => match span.metadata.get("status.code").map(String::as_str) {
+                Some("Ok") => DARTMOUTH_GREEN,
+                _ => SLATE,
+            },
+            Self::Kind => match span.kind {
+                SpanKind::Unspecified => SLATE,
+                SpanKind::Internal => DARTMOUTH_GREEN,
+                SpanKind::Server => CERULEAN,
+                SpanKind::Client => ROBIN_EGG_BLUE,
+                SpanKind::Producer => SAFFRON,
+                SpanKind::Consumer => ULTRA_VIOLET,
+            },
+            Self::Attribute(key) if !span.attributes.contains_key(key) => SLATE,
+            _ => hashed(&self.key(span)),
+        }
+    }
+}
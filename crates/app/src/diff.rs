@@ -0,0 +1,272 @@
+use std::rc::Rc;
+
+use eframe::egui::{
+    Align2, Color32, FontId, Grid, Pos2, Rect, Rounding, ScrollArea, Sense, Ui, Vec2,
+};
+use lib::{
+    diff::{diff, Change, DiffRow, TraceDiff},
+    format_duration, Span, Trace,
+};
+
+use crate::colors;
+
+/// Colors for each kind of [`Change`].
+const ADDED: Color32 = colors::ROBIN_EGG_BLUE;
+const REMOVED: Color32 = colors::ULTRA_VIOLET;
+const SLOWER: Color32 = colors::INDIAN_RED;
+const FASTER: Color32 = colors::DARTMOUTH_GREEN;
+
+/// Spans whose duration changed by less than this fraction of the
+/// baseline root span are treated as unchanged.
+const UNCHANGED_FRACTION: f64 = 0.01;
+
+/// Height of each row.
+const ROW_HEIGHT: f32 = 20.0;
+
+/// Widths of the text columns.
+const NAME_COLUMN_WIDTH: f32 = 320.0;
+const DURATION_COLUMN_WIDTH: f32 = 80.0;
+const DELTA_COLUMN_WIDTH: f32 = 150.0;
+
+/// Horizontal space between columns.
+const COLUMN_SPACING: f32 = 10.0;
+
+/// View options for a single diff tab. Kept across frames by
+/// [`crate::TabViewer`].
+#[derive(Debug, Default)]
+pub(crate) struct State {
+    /// Hide rows present in both traces whose duration barely changed.
+    only_changed: bool,
+
+    /// Last computed diff, along with the span counts of both traces
+    /// it was computed from.
+    diff: Option<((usize, usize), Rc<TraceDiff>)>,
+    /// Indices into [`TraceDiff::rows`] currently shown, along with
+    /// the value of [`Self::only_changed`] they were filtered with.
+    visible: Option<(bool, Rc<Vec<usize>>)>,
+}
+
+/// Combined waterfall of two traces aligned by span path. Each row
+/// shows the baseline (left) span above the compared (right) span.
+pub(crate) struct Diff<'a> {
+    left: &'a Trace,
+    right: &'a Trace,
+    state: &'a mut State,
+}
+
+impl<'a> Diff<'a> {
+    pub(crate) fn new(left: &'a Trace, right: &'a Trace, state: &'a mut State) -> Self {
+        Self { left, right, state }
+    }
+
+    fn diff(&mut self) -> Rc<TraceDiff> {
+        let key = (self.left.spans.len(), self.right.spans.len());
+        match &self.state.diff {
+            Some((cached, diff)) if *cached == key => diff.clone(),
+            _ => {
+                let diff = Rc::new(diff(self.left, self.right));
+                self.state.diff = Some((key, diff.clone()));
+                self.state.visible = None;
+                diff
+            }
+        }
+    }
+
+    fn visible(&mut self, diff: &TraceDiff) -> Rc<Vec<usize>> {
+        match &self.state.visible {
+            Some((only_changed, visible)) if *only_changed == self.state.only_changed => {
+                visible.clone()
+            }
+            _ => {
+                let visible = Rc::new(
+                    (0..diff.rows.len())
+                        .filter(|&i| !self.state.only_changed || self.is_changed(&diff.rows[i]))
+                        .collect::<Vec<_>>(),
+                );
+                self.state.visible = Some((self.state.only_changed, visible.clone()));
+                visible
+            }
+        }
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn is_changed(&self, row: &DiffRow) -> bool {
+        match row.change {
+            Change::Added | Change::Removed => true,
+            Change::Delta(delta) => {
+                delta.abs() as f64 >= self.left.spans[0].duration_nanos as f64 * UNCHANGED_FRACTION
+            }
+        }
+    }
+
+    fn color(&self, ui: &Ui, row: &DiffRow) -> Color32 {
+        match row.change {
+            Change::Added => ADDED,
+            Change::Removed => REMOVED,
+            _ if !self.is_changed(row) => ui.visuals().weak_text_color(),
+            Change::Delta(delta) if delta > 0 => SLOWER,
+            Change::Delta(_) => FASTER,
+        }
+    }
+
+    /// Paint a single row: name, both durations, the change, and both
+    /// spans' bars on a shared time scale.
+    #[allow(clippy::cast_precision_loss, clippy::cast_possible_truncation)]
+    fn draw_row(&self, ui: &mut Ui, row: &DiffRow, scale_nanos: i64) {
+        let (rect, _) =
+            ui.allocate_exact_size(Vec2::new(ui.available_width(), ROW_HEIGHT), Sense::hover());
+        if !ui.is_rect_visible(rect) {
+            return;
+        }
+        let visuals = ui.visuals();
+        let font = FontId::proportional(13.0);
+        let color = self.color(ui, row);
+
+        let mut left = rect.left();
+        let mut column = |width: f32| {
+            let column = Rect::from_x_y_ranges(left..=left + width, rect.y_range());
+            left += width + COLUMN_SPACING;
+            column
+        };
+        let text = |column: Rect, align: Align2, text: String, color: Color32| {
+            let anchor = Pos2::new(
+                column.left() + align.x().to_factor() * column.width(),
+                column.center().y,
+            );
+            ui.painter()
+                .with_clip_rect(column.intersect(ui.clip_rect()))
+                .text(anchor, align, text, font.clone(), color);
+        };
+
+        let name = column(NAME_COLUMN_WIDTH);
+        let indent = 15.0 * row.level as f32;
+        ui.painter().rect_filled(
+            Rect::from_min_size(
+                Pos2::new(name.left() + indent, name.top() + 2.0),
+                Vec2::new(5.0, ROW_HEIGHT - 4.0),
+            ),
+            Rounding::same(2.0),
+            color,
+        );
+        let label = if row.service.is_empty() {
+            row.name.clone()
+        } else {
+            format!("{} ({})", row.name, row.service)
+        };
+        let mut name_text = name;
+        name_text.min.x += indent + 10.0;
+        text(name_text, Align2::LEFT_CENTER, label, visuals.text_color());
+
+        let duration = |span: Option<&Span>| {
+            span.map_or("-".to_string(), |span| format_duration(span.duration_nanos))
+        };
+        let left_span = row.left.map(|i| &self.left.spans[i]);
+        let right_span = row.right.map(|i| &self.right.spans[i]);
+        text(
+            column(DURATION_COLUMN_WIDTH),
+            Align2::RIGHT_CENTER,
+            duration(left_span),
+            visuals.text_color(),
+        );
+        text(
+            column(DURATION_COLUMN_WIDTH),
+            Align2::RIGHT_CENTER,
+            duration(right_span),
+            visuals.text_color(),
+        );
+        let change = match (row.change, left_span) {
+            (Change::Added, _) => "added".to_string(),
+            (Change::Removed, _) => "removed".to_string(),
+            (Change::Delta(delta), Some(left_span)) => format!(
+                "{} ({:+.1}%)",
+                signed_duration(delta),
+                delta as f64 / left_span.duration_nanos.max(1) as f64 * 100.0
+            ),
+            (Change::Delta(delta), None) => signed_duration(delta),
+        };
+        text(
+            column(DELTA_COLUMN_WIDTH),
+            Align2::RIGHT_CENTER,
+            change,
+            color,
+        );
+
+        let bars = Rect::from_x_y_ranges(left..=rect.right().max(left + 1.0), rect.y_range());
+        let x_at =
+            |nanos: i64| bars.left() + (nanos as f64 / scale_nanos as f64) as f32 * bars.width();
+        let bar = |span: &Span, top: f32, color: Color32| {
+            let start = x_at(span.offset_nanos);
+            let end = x_at(span.end_offset_nanos()).max(start + 2.0);
+            // async spans may outlive the root span
+            ui.painter()
+                .with_clip_rect(bars.intersect(ui.clip_rect()))
+                .rect_filled(
+                    Rect::from_x_y_ranges(start..=end, top..=top + ROW_HEIGHT / 2.0 - 2.0),
+                    Rounding::same(2.0),
+                    color,
+                );
+        };
+        if let Some(span) = left_span {
+            bar(span, rect.top() + 1.0, visuals.weak_text_color());
+        }
+        if let Some(span) = right_span {
+            bar(span, rect.center().y + 1.0, color);
+        }
+    }
+}
+
+impl<'a> crate::Panel for Diff<'a> {
+    fn draw(&mut self, ui: &mut Ui) -> Option<crate::Action> {
+        let diff = self.diff();
+
+        ui.heading(format!("Diff: {} → {}", self.left.id, self.right.id));
+        let (left_root, right_root) = (&self.left.spans[0], &self.right.spans[0]);
+        Grid::new("diff_summary").num_columns(3).show(ui, |ui| {
+            ui.label("Baseline");
+            ui.label(&self.left.id);
+            ui.label(format_duration(left_root.duration_nanos));
+            ui.end_row();
+
+            ui.label("Compared");
+            ui.label(&self.right.id);
+            ui.label(format_duration(right_root.duration_nanos));
+            ui.end_row();
+        });
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.state.only_changed, "Only changed spans");
+            ui.separator();
+            for (color, label) in [
+                (SLOWER, "slower"),
+                (FASTER, "faster"),
+                (ADDED, "added"),
+                (REMOVED, "removed"),
+            ] {
+                let (swatch, _) = ui.allocate_exact_size(Vec2::splat(10.0), Sense::hover());
+                ui.painter().rect_filled(swatch, Rounding::same(2.0), color);
+                ui.label(label);
+            }
+        });
+        ui.add_space(5.0);
+
+        let visible = self.visible(&diff);
+        let scale_nanos = left_root
+            .duration_nanos
+            .max(right_root.duration_nanos)
+            .max(1);
+        ScrollArea::vertical().show_rows(ui, ROW_HEIGHT, visible.len(), |ui, range| {
+            for i in range {
+                self.draw_row(ui, &diff.rows[visible[i]], scale_nanos);
+            }
+        });
+        None
+    }
+}
+
+/// [`format_duration`] with a leading `+` or `-`.
+fn signed_duration(nanos: i64) -> String {
+    if nanos < 0 {
+        format!("-{}", format_duration(-nanos))
+    } else {
+        format!("+{}", format_duration(nanos))
+    }
+}
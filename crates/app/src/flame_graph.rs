@@ -0,0 +1,297 @@
+use eframe::egui::{
+    self, Align2, Color32, FontId, Pos2, Rect, Rounding, ScrollArea, Sense, Stroke, Ui, Vec2,
+};
+use std::sync::{Arc, Mutex};
+
+use lib::{
+    flame_graph::{flame_graph, FlameGraph as Graph, Frame},
+    format_duration, Trace,
+};
+
+/// Height of each flame graph row.
+const FRAME_HEIGHT: f32 = 20.0;
+
+/// Frames narrower than this, in points, aren't drawn.
+const MIN_FRAME_WIDTH: f32 = 0.5;
+
+/// Frames narrower than this, in points, aren't labelled.
+const MIN_LABEL_WIDTH: f32 = 24.0;
+
+/// View options for a flame graph. Kept across frames by the owner of
+/// the graph.
+#[derive(Debug, Default)]
+pub(crate) struct State {
+    /// Names along the path to the frame zoomed into.
+    zoom: Vec<String>,
+
+    /// Frames whose name contains this (case-insensitive) are
+    /// highlighted.
+    search: String,
+}
+
+impl State {
+    /// Index of the frame at the end of [`Self::zoom`], if it still
+    /// exists in `graph`.
+    fn zoomed(&self, graph: &Graph) -> Option<usize> {
+        let mut siblings = &graph.roots;
+        let mut found = None;
+        for name in &self.zoom {
+            let idx = *siblings
+                .iter()
+                .find(|&&idx| &graph.frames[idx].name == name)?;
+            siblings = &graph.frames[idx].children;
+            found = Some(idx);
+        }
+        found
+    }
+
+    fn zoom_to(&mut self, graph: &Graph, idx: usize) {
+        self.zoom = graph
+            .path_to(idx)
+            .into_iter()
+            .map(|idx| graph.frames[idx].name.clone())
+            .collect();
+    }
+}
+
+/// Icicle-style flame graph: roots on top, each frame as wide as the
+/// time spent beneath it. Clicking a frame zooms into it.
+pub(crate) struct FlameGraph<'a> {
+    graph: &'a Graph,
+    state: &'a mut State,
+}
+
+impl<'a> FlameGraph<'a> {
+    pub(crate) fn new(graph: &'a Graph, state: &'a mut State) -> Self {
+        Self { graph, state }
+    }
+
+    /// Number of frames matching `search`, and the time within
+    /// `within` spent in them, counting nested matches only once.
+    fn matched_nanos(&self, search: &str, within: (i64, i64)) -> (usize, i64) {
+        let mut count = 0;
+        let mut nanos = 0;
+        for (idx, frame) in self.graph.frames.iter().enumerate() {
+            if !matches(frame, search) {
+                continue;
+            }
+            count += 1;
+            let nested = self.graph.path_to(idx)[..frame.depth]
+                .iter()
+                .any(|&ancestor| matches(&self.graph.frames[ancestor], search));
+            if !nested {
+                let start = frame.offset_nanos.max(within.0);
+                let end = frame.end_offset_nanos().min(within.1);
+                nanos += (end - start).max(0);
+            }
+        }
+        (count, nanos)
+    }
+}
+
+impl<'a> crate::Panel for FlameGraph<'a> {
+    #[allow(clippy::cast_precision_loss)]
+    fn draw(&mut self, ui: &mut Ui) -> Option<crate::Action> {
+        let zoomed = self.state.zoomed(self.graph);
+        let (start, end) = zoomed.map_or((0, self.graph.total_nanos()), |idx| {
+            let frame = &self.graph.frames[idx];
+            (frame.offset_nanos, frame.end_offset_nanos())
+        });
+        let len = (end - start).max(1);
+
+        ui.horizontal(|ui| {
+            ui.label("Search");
+            ui.text_edit_singleline(&mut self.state.search);
+            ui.separator();
+            if ui
+                .add_enabled(zoomed.is_some(), egui::Button::new("Reset zoom"))
+                .clicked()
+            {
+                self.state.zoom.clear();
+            }
+            if let Some(zoomed) = zoomed {
+                for (i, idx) in self.graph.path_to(zoomed).into_iter().enumerate() {
+                    if i > 0 {
+                        ui.weak("›");
+                    }
+                    if ui.link(&self.graph.frames[idx].name).clicked() {
+                        self.state.zoom_to(self.graph, idx);
+                    }
+                }
+            }
+        });
+        let search = self.state.search.to_lowercase();
+        if !search.is_empty() {
+            let (count, nanos) = self.matched_nanos(&search, (start, end));
+            ui.label(format!(
+                "{count} matching frames, {:.1}% of the visible time",
+                nanos as f64 / len as f64 * 100.0
+            ));
+        }
+        ui.add_space(5.0);
+
+        ScrollArea::vertical().show(ui, |ui| {
+            let height = (self.graph.max_depth() + 1) as f32 * FRAME_HEIGHT;
+            let (rect, response) =
+                ui.allocate_exact_size(Vec2::new(ui.available_width(), height), Sense::click());
+            let x_at = |nanos: i64| {
+                rect.left() + ((nanos - start) as f64 / len as f64) as f32 * rect.width()
+            };
+
+            let painter = ui.painter().with_clip_rect(rect.intersect(ui.clip_rect()));
+            let visuals = ui.visuals();
+            let font = FontId::proportional(12.0);
+            let pointer = response.hover_pos();
+            let mut hovered = None;
+            for (idx, frame) in self.graph.frames.iter().enumerate() {
+                if frame.end_offset_nanos() <= start || frame.offset_nanos >= end {
+                    continue;
+                }
+                let left = x_at(frame.offset_nanos).max(rect.left());
+                let right = x_at(frame.end_offset_nanos()).min(rect.right());
+                if right - left < MIN_FRAME_WIDTH {
+                    continue;
+                }
+                let top = rect.top() + frame.depth as f32 * FRAME_HEIGHT;
+                let frame_rect = Rect::from_min_max(
+                    Pos2::new(left, top),
+                    Pos2::new(right, top + FRAME_HEIGHT - 1.0),
+                );
+                if !ui.is_rect_visible(frame_rect) {
+                    continue;
+                }
+                if pointer.is_some_and(|pointer| frame_rect.contains(pointer)) {
+                    hovered = Some(idx);
+                }
+
+                let matched = matches(frame, &search);
+                let color = crate::colors::hashed(&frame.name);
+                let color = if search.is_empty() || matched {
+                    color
+                } else {
+                    color.gamma_multiply(0.25)
+                };
+                painter.rect_filled(
+                    frame_rect.shrink2(Vec2::new(0.5, 0.0)),
+                    Rounding::same(2.0),
+                    color,
+                );
+                if matched || hovered == Some(idx) {
+                    painter.rect_stroke(
+                        frame_rect,
+                        Rounding::same(2.0),
+                        Stroke::new(1.5, visuals.strong_text_color()),
+                    );
+                }
+                if frame_rect.width() >= MIN_LABEL_WIDTH {
+                    ui.painter()
+                        .with_clip_rect(frame_rect.shrink(2.0).intersect(ui.clip_rect()))
+                        .text(
+                            frame_rect.left_center() + Vec2::new(4.0, 0.0),
+                            Align2::LEFT_CENTER,
+                            &frame.name,
+                            font.clone(),
+                            Color32::WHITE,
+                        );
+                }
+            }
+
+            if let Some(idx) = hovered {
+                let frame = &self.graph.frames[idx];
+                // merged from several traces: report the average per trace
+                #[allow(clippy::cast_possible_wrap)]
+                let traces = self.graph.traces.max(1) as i64;
+                let per_trace = |nanos: i64| format_duration(nanos / traces);
+                egui::show_tooltip_at_pointer(ui.ctx(), ui.id().with("flame_frame"), |ui| {
+                    ui.strong(&frame.name);
+                    ui.label(format!("spans: {}", frame.count));
+                    ui.label(format!(
+                        "total: {} ({:.1}%)",
+                        per_trace(frame.total_nanos),
+                        frame.total_nanos as f64 / len as f64 * 100.0
+                    ));
+                    ui.label(format!("self: {}", per_trace(frame.self_nanos)));
+                    ui.label(format!("duration: {}", per_trace(frame.duration_nanos)));
+                    if traces > 1 {
+                        ui.weak(format!("averaged over {traces} traces"));
+                    }
+                });
+                if response.clicked() {
+                    self.state.zoom_to(self.graph, idx);
+                }
+            }
+        });
+        None
+    }
+}
+
+/// Flame graph merged from a selection of traces, showing where time
+/// goes on average across them.
+pub(crate) struct Aggregate {
+    state: State,
+    traces: Arc<Mutex<Vec<Trace>>>,
+    /// IDs of the merged traces.
+    selection: Vec<String>,
+
+    /// Last computed graph, along with the [`crate::generation`] it was
+    /// computed from.
+    graph: Graph,
+    cache_key: Option<u64>,
+}
+
+impl Aggregate {
+    pub(crate) fn new(traces: Arc<Mutex<Vec<Trace>>>) -> Self {
+        Self {
+            state: State::default(),
+            traces,
+            selection: Vec::new(),
+            graph: Graph::default(),
+            cache_key: None,
+        }
+    }
+
+    /// Merge the traces with the IDs in `selection` instead.
+    pub(crate) fn select(&mut self, selection: Vec<String>) {
+        self.selection = selection;
+        self.state.zoom.clear();
+        self.cache_key = None;
+    }
+
+    pub(crate) fn trace_count(&self) -> usize {
+        self.selection.len()
+    }
+
+    /// Rebuild [`Self::graph`] if the selection or traces changed.
+    fn refresh(&mut self) {
+        let traces = self.traces.lock().unwrap();
+        let key = crate::generation();
+        if self.cache_key != Some(key) {
+            self.graph = flame_graph(
+                self.selection
+                    .iter()
+                    .filter_map(|id| crate::find_trace(&traces, id)),
+            );
+            self.cache_key = Some(key);
+        }
+    }
+}
+
+impl crate::Panel for Aggregate {
+    fn draw(&mut self, ui: &mut Ui) -> Option<crate::Action> {
+        self.refresh();
+        if self.graph.traces == 0 {
+            ui.label("Select traces in the trace list to merge them here.");
+            return None;
+        }
+        ui.label(format!(
+            "Merged from {} traces. Widths and times are averages per trace.",
+            self.graph.traces
+        ));
+        FlameGraph::new(&self.graph, &mut self.state).draw(ui)
+    }
+}
+
+/// Whether `frame` matches the already lowercased `search`.
+fn matches(frame: &Frame, search: &str) -> bool {
+    !search.is_empty() && frame.name.to_lowercase().contains(search)
+}
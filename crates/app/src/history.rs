@@ -0,0 +1,342 @@
+use std::{
+    collections::HashSet,
+    rc::Rc,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use eframe::egui::{self, ComboBox, Grid, TextEdit, Ui};
+use egui_extras::{Column, TableBuilder};
+use lib::{
+    build_traces, format_duration,
+    store::{Filter, SegmentLog, StoredTrace},
+    Trace,
+};
+use tracing::error;
+
+use crate::{live::Live, SharedStore};
+
+/// Number of most recently started traces loaded from the store on
+/// startup.
+const RESTORE_LIMIT: usize = 1_000;
+
+/// Traces loaded at a time while restoring, so the list fills in as
+/// they load.
+const RESTORE_BATCH: usize = 50;
+
+/// Most traces listed at once.
+const HISTORY_ROWS: usize = 500;
+
+/// How often a listing limited to recent traces is queried again, so
+/// traces fall out of it as they age.
+const REQUERY_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How soon to try again when the store is busy.
+const STORE_RETRY: Duration = Duration::from_millis(100);
+
+/// How recently listed traces must have started.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum Since {
+    LastHour,
+    LastDay,
+    LastWeek,
+    #[default]
+    Any,
+}
+
+impl Since {
+    const ALL: [Self; 4] = [Self::LastHour, Self::LastDay, Self::LastWeek, Self::Any];
+
+    fn label(self) -> &'static str {
+        match self {
+            Self::LastHour => "Last hour",
+            Self::LastDay => "Last day",
+            Self::LastWeek => "Last week",
+            Self::Any => "Any time",
+        }
+    }
+
+    fn duration(self) -> Option<chrono::Duration> {
+        match self {
+            Self::LastHour => Some(chrono::Duration::hours(1)),
+            Self::LastDay => Some(chrono::Duration::days(1)),
+            Self::LastWeek => Some(chrono::Duration::weeks(1)),
+            Self::Any => None,
+        }
+    }
+}
+
+/// Every trace saved to the store, including ones from earlier
+/// sessions and ones dropped from memory, which are loaded back when
+/// opened.
+pub(crate) struct History {
+    traces: Arc<Mutex<Vec<Trace>>>,
+    store: Option<SharedStore>,
+    /// Told to keep traces being opened, so they aren't dropped again
+    /// before their tab is.
+    live: Arc<Mutex<Live>>,
+
+    /// Only list traces whose root span is from this service.
+    service: String,
+    /// Only list traces whose root span name contains this.
+    name: String,
+    since: Since,
+
+    /// Error from the last load, if it failed.
+    error: Option<String>,
+
+    /// Result of the last query of the store.
+    listing: Option<Listing>,
+    /// IDs of the traces in memory, and the [`crate::generation`]
+    /// they were collected at.
+    loaded: Rc<HashSet<String>>,
+    loaded_key: u64,
+}
+
+/// Traces listed by a query, along with the filters and
+/// [`lib::store::Store::revision`] it ran with.
+struct Listing {
+    key: (String, String, Since, u64),
+    at: Instant,
+    traces: Rc<Vec<StoredTrace>>,
+}
+
+impl History {
+    pub(crate) fn new(
+        traces: Arc<Mutex<Vec<Trace>>>,
+        store: Option<SharedStore>,
+        live: Arc<Mutex<Live>>,
+    ) -> Self {
+        Self {
+            traces,
+            store,
+            live,
+            service: String::new(),
+            name: String::new(),
+            since: Since::default(),
+            error: None,
+            listing: None,
+            loaded: Rc::default(),
+            loaded_key: 0,
+        }
+    }
+
+    /// Stored traces matching the filters, querying `store` again only
+    /// when they or it have changed, or a time window has moved on.
+    /// Keeps the last listing while the store is busy.
+    fn stored(&mut self, store: &SharedStore, ui: &Ui) -> Rc<Vec<StoredTrace>> {
+        let Ok(store) = store.try_lock() else {
+            ui.ctx().request_repaint_after(STORE_RETRY);
+            return self
+                .listing
+                .as_ref()
+                .map(|listing| listing.traces.clone())
+                .unwrap_or_default();
+        };
+        let key = (
+            self.service.clone(),
+            self.name.clone(),
+            self.since,
+            store.revision(),
+        );
+        if let Some(listing) = &self.listing {
+            let aged = self.since != Since::Any && listing.at.elapsed() >= REQUERY_INTERVAL;
+            if listing.key == key && !aged {
+                return listing.traces.clone();
+            }
+        }
+        let non_empty = |text: &str| Some(text.trim().to_string()).filter(|text| !text.is_empty());
+        let filter = Filter {
+            service: non_empty(&self.service),
+            name: non_empty(&self.name),
+            since: self.since.duration().map(|age| chrono::Utc::now() - age),
+            until: None,
+            limit: Some(HISTORY_ROWS),
+        };
+        let traces = Rc::new(store.traces(&filter));
+        self.listing = Some(Listing {
+            key,
+            at: Instant::now(),
+            traces: traces.clone(),
+        });
+        traces
+    }
+
+    /// IDs of the traces in memory.
+    fn loaded(&mut self) -> Rc<HashSet<String>> {
+        let traces = self.traces.lock().unwrap();
+        let key = crate::generation();
+        if key != self.loaded_key {
+            self.loaded = Rc::new(traces.iter().map(|trace| trace.id.clone()).collect());
+            self.loaded_key = key;
+        }
+        self.loaded.clone()
+    }
+
+    fn draw_filters(&mut self, ui: &mut Ui) {
+        Grid::new("history_filters").num_columns(2).show(ui, |ui| {
+            ui.label("Service");
+            ui.add(TextEdit::singleline(&mut self.service).hint_text("service.name"));
+            ui.end_row();
+
+            ui.label("Name");
+            ui.add(TextEdit::singleline(&mut self.name).hint_text("root span name"));
+            ui.end_row();
+
+            ui.label("Started");
+            ComboBox::from_id_source("history_since")
+                .selected_text(self.since.label())
+                .show_ui(ui, |ui| {
+                    for since in Since::ALL {
+                        ui.selectable_value(&mut self.since, since, since.label());
+                    }
+                });
+            ui.end_row();
+        });
+    }
+}
+
+impl crate::Panel for History {
+    fn draw(&mut self, ui: &mut Ui) -> Option<crate::Action> {
+        let Some(store) = self.store.clone() else {
+            ui.label("Traces aren't being saved, as the store couldn't be opened.");
+            return None;
+        };
+        self.draw_filters(ui);
+        ui.add_space(5.0);
+
+        let stored = self.stored(&store, ui);
+        let loaded = self.loaded();
+        if stored.len() == HISTORY_ROWS {
+            ui.label(format!("Latest {HISTORY_ROWS} traces"));
+        } else {
+            ui.label(format!("{} traces", stored.len()));
+        }
+        if let Some(err) = &self.error {
+            ui.colored_label(
+                ui.visuals().error_fg_color,
+                format!("Loading failed: {err}"),
+            );
+        }
+        ui.add_space(5.0);
+
+        let mut open = None;
+        TableBuilder::new(ui)
+            .striped(true)
+            .column(Column::auto().at_least(250.0))
+            .column(Column::auto().at_least(150.0))
+            .column(Column::auto().at_least(120.0))
+            .column(Column::auto().at_least(150.0))
+            .columns(Column::auto().at_least(70.0), 2)
+            .header(20.0, |mut header| {
+                for text in ["Trace ID", "Name", "Service", "Start", "Duration", "Spans"] {
+                    header.col(|ui| {
+                        ui.heading(text);
+                    });
+                }
+            })
+            .body(|mut body| {
+                for trace in stored.iter() {
+                    body.row(20.0, |mut row| {
+                        row.col(|ui| {
+                            if ui.link(&trace.id).clicked() {
+                                open = Some(trace.id.clone());
+                            }
+                            if !loaded.contains(&trace.id) {
+                                ui.weak("(on disk)")
+                                    .on_hover_text("Loaded into memory when opened");
+                            }
+                        });
+                        row.col(|ui| {
+                            ui.label(&trace.name);
+                        });
+                        row.col(|ui| {
+                            ui.label(trace.service.as_deref().unwrap_or("-"));
+                        });
+                        row.col(|ui| {
+                            ui.label(trace.start.format("%b %e, %H:%M:%S%.3f").to_string());
+                        });
+                        row.col(|ui| {
+                            ui.label(format_duration(trace.duration_nanos));
+                        });
+                        row.col(|ui| {
+                            ui.label(trace.span_count.to_string());
+                        });
+                    });
+                }
+            });
+
+        let id = open?;
+        if !loaded.contains(&id) {
+            self.live.lock().unwrap().keep(&id);
+            self.error = load(&store, &self.traces, std::slice::from_ref(&id)).err();
+            if self.error.is_some() {
+                return None;
+            }
+        }
+        Some(crate::Action::OpenTraceDetails(id))
+    }
+
+    fn refresh_after(&self) -> Option<Duration> {
+        (self.since != Since::Any).then_some(REQUERY_INTERVAL)
+    }
+}
+
+/// Load the traces with `ids` from `store` into `traces`, skipping
+/// ones already there.
+fn load(store: &SharedStore, traces: &Mutex<Vec<Trace>>, ids: &[String]) -> Result<(), String> {
+    let mut spans = Vec::new();
+    {
+        let store = store.lock().unwrap();
+        for id in ids {
+            spans.append(&mut store.load(id)?);
+        }
+    }
+    let mut loaded = build_traces(spans)?;
+    let mut traces = traces.lock().unwrap();
+    let present = traces
+        .iter()
+        .map(|trace| trace.id.clone())
+        .collect::<HashSet<_>>();
+    loaded.retain(|trace| !present.contains(&trace.id));
+    traces.append(&mut loaded);
+    crate::bump_generation();
+    Ok(())
+}
+
+/// Index what earlier sessions saved to `log`, then load the most
+/// recently started traces into `traces`, all in the background,
+/// repainting `ctx` as each batch arrives. Older traces are loaded
+/// first, so retention drops them first.
+pub(crate) fn restore(
+    log: Arc<Mutex<SegmentLog>>,
+    traces: Arc<Mutex<Vec<Trace>>>,
+    ctx: egui::Context,
+) {
+    tokio::task::spawn_blocking(move || {
+        if let Err(err) = SegmentLog::index(&log) {
+            error!("indexing trace store: {err}");
+        }
+        ctx.request_repaint();
+        let store: SharedStore = log;
+        let filter = Filter {
+            limit: Some(RESTORE_LIMIT),
+            ..Filter::default()
+        };
+        let mut ids = store
+            .lock()
+            .unwrap()
+            .traces(&filter)
+            .into_iter()
+            .map(|trace| trace.id)
+            .collect::<Vec<_>>();
+        ids.reverse();
+        for batch in ids.chunks(RESTORE_BATCH) {
+            if let Err(err) = load(&store, &traces, batch) {
+                error!("restoring traces from store: {err}");
+                return;
+            }
+            ctx.request_repaint();
+        }
+    });
+}
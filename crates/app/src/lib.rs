@@ -1,23 +1,93 @@
 mod attributes;
 pub mod collector;
+mod colors;
+pub mod diff;
+pub mod flame_graph;
+mod history;
 pub mod list;
+mod live;
+mod scatter;
+pub mod service_graph;
 pub mod settings;
+pub mod stats;
 pub mod waterfall;
 
 use egui_dock::Tree;
-use lib::{build_traces, parse_file, Span, Trace};
+use lib::{
+    build_traces, parse_file,
+    retention::{Retention, Usage},
+    store::{SegmentLog, Store},
+    Span, Trace,
+};
 use tokio::sync::mpsc;
 
 use std::{
+    collections::{HashMap, HashSet},
     path::Path,
-    sync::{Arc, Mutex},
-    time::Duration,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc::RecvTimeoutError,
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
 };
 
 use eframe::egui::{self, menu, InputState, Layout};
 
 use tracing::error;
 
+/// [`Store`] shared by everything that saves or loads spans.
+type SharedStore = Arc<Mutex<dyn Store>>;
+
+/// Appends spans to a [`SharedStore`] on a thread of its own, so file
+/// I/O holds up neither drawing nor the collector. The store is also
+/// pruned every [`PRUNE_INTERVAL`], so its age limit applies even
+/// while few spans arrive.
+#[derive(Debug, Clone)]
+struct StoreWriter(std::sync::mpsc::Sender<Vec<Span>>);
+
+impl StoreWriter {
+    fn spawn(store: SharedStore) -> Self {
+        let (tx, rx) = std::sync::mpsc::channel::<Vec<Span>>();
+        std::thread::spawn(move || {
+            let mut pruned = Instant::now();
+            loop {
+                match rx.recv_timeout(PRUNE_INTERVAL) {
+                    Ok(spans) => {
+                        if let Err(err) = store.lock().unwrap().append(&spans) {
+                            error!("saving spans to store: {err}");
+                        }
+                    }
+                    Err(RecvTimeoutError::Timeout) => {}
+                    Err(RecvTimeoutError::Disconnected) => break,
+                }
+                if pruned.elapsed() >= PRUNE_INTERVAL {
+                    if let Err(err) = store.lock().unwrap().prune() {
+                        error!("pruning trace store: {err}");
+                    }
+                    pruned = Instant::now();
+                }
+            }
+        });
+        Self(tx)
+    }
+
+    /// Queue `spans` to be saved. Failures are logged rather than
+    /// returned, as the spans are still usable in memory.
+    fn save(&self, spans: Vec<Span>) {
+        if self.0.send(spans).is_err() {
+            error!("saving spans to store: writer thread stopped");
+        }
+    }
+}
+
+/// How often traces are checked against an age limit when nothing
+/// else changed.
+const RETENTION_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How often [`StoreWriter`] prunes the store.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(10);
+
 /// Floating window that can be collapsed or dismissed.
 trait Panel {
     /// Draw contents of [`Panel`]. Surrounding
@@ -33,30 +103,66 @@ trait Panel {
     }
 }
 
+/// Traces and spans are referred to by ID rather than by index, here,
+/// in [`Tab`] and in the state kept for each tab, as indices change
+/// whenever the collector rebuilds the traces.
 #[derive(Debug)]
 enum Action {
-    /// Open attributes tab for [`crate::Span`] at index. Parent
+    /// Open attributes tab for [`crate::Span`] with ID. Parent
     /// [`crate::Trace`] is implied by context.
-    OpenSpanAttributes(usize),
-    /// Open trace details tab for [`crate::Trace`] at index.
-    OpenTraceDetails(usize),
+    OpenSpanAttributes(String),
+    /// Open trace details tab for [`crate::Trace`] with ID.
+    OpenTraceDetails(String),
+    /// Open a trace details tab for each [`crate::Trace`] with the
+    /// IDs.
+    OpenTraces(Vec<String>),
+    /// Open a flame graph merged from the [`crate::Trace`]s with the
+    /// IDs.
+    OpenAggregateFlameGraph(Vec<String>),
+    /// Open a diff tab comparing the [`crate::Trace`] with the second
+    /// ID against the baseline with the first.
+    OpenTraceDiff(String, String),
+    /// Show trace list, filtered to traces containing the operation.
+    FilterTracesByOperation { service: String, name: String },
+    /// Show trace list, filtered to traces where `caller` calls `callee`.
+    FilterTracesByServiceCall { caller: String, callee: String },
 }
 
 #[derive(Debug, Clone)]
 enum Tab {
+    AggregateFlameGraph,
     Appearance,
     Collector,
-    SpanAttributes(usize, usize),
-    TraceDetails(usize),
+    History,
+    ServiceGraph,
+    /// Trace ID, span ID.
+    SpanAttributes(String, String),
+    Statistics,
+    /// Trace ID.
+    TraceDetails(String),
+    /// Baseline and compared trace IDs.
+    TraceDiff(String, String),
     TraceList,
 }
 
+impl Tab {
+    /// IDs of the traces shown in this tab.
+    fn trace_ids(&self) -> Vec<&String> {
+        match self {
+            Self::SpanAttributes(trace_id, _) | Self::TraceDetails(trace_id) => vec![trace_id],
+            Self::TraceDiff(left_id, right_id) => vec![left_id, right_id],
+            _ => Vec::new(),
+        }
+    }
+}
+
 impl PartialEq for Tab {
     fn eq(&self, other: &Self) -> bool {
         match (self, other) {
             // only allow a single attributes panel to be open
             (Self::SpanAttributes(_, _), Self::SpanAttributes(_, _)) => true,
             (Self::TraceDetails(l0), Self::TraceDetails(r0)) => l0 == r0,
+            (Self::TraceDiff(l0, l1), Self::TraceDiff(r0, r1)) => (l0, l1) == (r0, r1),
             _ => core::mem::discriminant(self) == core::mem::discriminant(other),
         }
     }
@@ -66,44 +172,193 @@ struct TabViewer {
     settings: settings::Settings,
     traces: Arc<Mutex<Vec<Trace>>>,
 
+    aggregate: flame_graph::Aggregate,
     collector: collector::Collector,
+    history: history::History,
     list: list::TraceList,
+    /// Shared with the collector and list, see [`live::Live`].
+    live: Arc<Mutex<live::Live>>,
+    store: Option<SharedStore>,
+    stats: stats::Statistics,
+    service_graph: service_graph::ServiceGraph,
+    /// Per-trace [`waterfall::State`], keyed by trace ID.
+    waterfalls: HashMap<String, waterfall::State>,
+    /// Per-pair [`diff::State`], keyed by (baseline, compared) trace
+    /// ID.
+    diffs: HashMap<(String, String), diff::State>,
+    /// Copy of each trace drawn in a details or diff tab, so drawing
+    /// doesn't hold the traces lock. Replaced when the trace gains
+    /// spans.
+    snapshots: HashMap<String, Arc<Trace>>,
 
     /// [`Tab`]s to be added/updated after previous frame.
     pub(crate) last_frame_tabs: Vec<Tab>,
+
+    /// When [`Self::enforce_retention`] last ran, along with the
+    /// [`generation`] it left the traces at and the limits it applied.
+    retained: Option<(Instant, u64, Retention)>,
+    /// Limits last passed on by [`Self::enforce_storage`].
+    stored: Option<Retention>,
 }
 
 impl TabViewer {
-    fn new(traces: Arc<Mutex<Vec<Trace>>>) -> Self {
+    fn new(
+        traces: Arc<Mutex<Vec<Trace>>>,
+        store: Option<SharedStore>,
+        writer: Option<StoreWriter>,
+    ) -> Self {
+        let live = Arc::<Mutex<live::Live>>::default();
         Self {
             settings: crate::settings::Settings::default(),
             traces: traces.clone(),
-            collector: collector::Collector::new(traces.clone()),
-            list: list::TraceList::new(traces),
+            aggregate: flame_graph::Aggregate::new(traces.clone()),
+            collector: collector::Collector::new(traces.clone(), live.clone(), writer),
+            history: history::History::new(traces.clone(), store.clone(), live.clone()),
+            list: list::TraceList::new(traces.clone(), live.clone()),
+            live,
+            store,
+            stats: stats::Statistics::new(traces.clone()),
+            service_graph: service_graph::ServiceGraph::new(traces),
+            waterfalls: HashMap::new(),
+            diffs: HashMap::new(),
+            snapshots: HashMap::new(),
             last_frame_tabs: Vec::new(),
+            retained: None,
+            stored: None,
         }
     }
+
+    /// Pass [`settings::Settings::storage`] on to the store when it
+    /// changes.
+    fn enforce_storage(&mut self) {
+        let storage = self.settings.storage;
+        if self.stored == Some(storage) {
+            return;
+        }
+        if let Some(store) = &self.store {
+            if let Err(err) = store.lock().unwrap().set_retention(storage) {
+                error!("pruning trace store: {err}");
+            }
+        }
+        self.stored = Some(storage);
+    }
+
+    /// Drop traces beyond [`settings::Settings::retention`], other
+    /// than pinned ones and the `open` ones shown in tabs, along with
+    /// the state of their tabs, and update the usage shown in
+    /// settings. Also passes the limits on to [`live::Live`], which
+    /// applies them as spans arrive. Skipped unless the traces or
+    /// limits changed, or an age limit is due to be checked again.
+    fn enforce_retention(&mut self, open: HashSet<String>) {
+        let retention = self.settings.retention;
+        let mut kept = open;
+        kept.extend(self.list.pinned().iter().cloned());
+        let mut live = self.live.lock().unwrap();
+        let mut traces = self.traces.lock().unwrap();
+        if let Some((at, retained, applied)) = self.retained {
+            let aging = retention.max_age.is_some() && at.elapsed() >= RETENTION_INTERVAL;
+            if retained == generation() && applied == retention && !aging {
+                live.set_retention(retention, kept);
+                return;
+            }
+        }
+        if retention.apply(&mut traces, chrono::Utc::now(), |trace| {
+            kept.contains(&trace.id)
+        }) > 0
+        {
+            bump_generation();
+        }
+        live.set_retention(retention, kept);
+        // traces may also have been dropped by `live`
+        let ids = traces
+            .iter()
+            .map(|trace| trace.id.as_str())
+            .collect::<HashSet<_>>();
+        self.waterfalls.retain(|id, _| ids.contains(id.as_str()));
+        self.diffs
+            .retain(|(left, right), _| ids.contains(left.as_str()) && ids.contains(right.as_str()));
+        self.snapshots.retain(|id, _| ids.contains(id.as_str()));
+        self.settings.usage = Usage::of(&traces);
+        self.retained = Some((Instant::now(), generation(), retention));
+    }
+
+    /// Current [`Self::snapshots`] entry for the trace with `id`, if
+    /// it is still in memory.
+    fn snapshot(&mut self, id: &str) -> Option<Arc<Trace>> {
+        let traces = self.traces.lock().unwrap();
+        let Some(trace) = find_trace(&traces, id) else {
+            self.snapshots.remove(id);
+            return None;
+        };
+        match self.snapshots.get(id) {
+            Some(snapshot) if snapshot.spans.len() == trace.spans.len() => Some(snapshot.clone()),
+            _ => {
+                let snapshot = Arc::new(trace.clone());
+                self.snapshots.insert(id.to_string(), snapshot.clone());
+                Some(snapshot)
+            }
+        }
+    }
+
+    /// Shortest [`Panel::refresh_after`] of the panels, whether or
+    /// not they are open.
+    fn refresh_after(&self) -> Option<Duration> {
+        [
+            self.aggregate.refresh_after(),
+            self.collector.refresh_after(),
+            self.history.refresh_after(),
+            self.list.refresh_after(),
+            self.service_graph.refresh_after(),
+            self.stats.refresh_after(),
+        ]
+        .into_iter()
+        .flatten()
+        .min()
+    }
 }
 
 impl egui_dock::TabViewer for TabViewer {
     type Tab = Tab;
 
     fn ui(&mut self, ui: &mut egui::Ui, tab: &mut Self::Tab) {
-        let (trace_idx, action) = match tab {
+        let (trace_id, action) = match tab {
+            Tab::AggregateFlameGraph => (None, self.aggregate.draw(ui)),
             Tab::Appearance => (None, settings::Panel(&mut self.settings).draw(ui)),
             Tab::Collector => (None, self.collector.draw(ui)),
-            Tab::SpanAttributes(trace_idx, span_idx) => {
-                if let Some(trace) = self.traces.lock().unwrap().get(*trace_idx).cloned() {
-                    let span = trace.spans[*span_idx].clone();
-                    (Some(*trace_idx), attributes::Attributes::new(span).draw(ui))
+            Tab::History => (None, self.history.draw(ui)),
+            Tab::ServiceGraph => (None, self.service_graph.draw(ui)),
+            Tab::SpanAttributes(trace_id, span_id) => {
+                let span = find_span(&self.traces.lock().unwrap(), trace_id, span_id).cloned();
+                if let Some(span) = span {
+                    (
+                        Some(trace_id.clone()),
+                        attributes::Attributes::new(span).draw(ui),
+                    )
                 } else {
                     (None, None)
                 }
             }
+            Tab::Statistics => (None, self.stats.draw(ui)),
             Tab::TraceList => (None, self.list.draw(ui)),
-            Tab::TraceDetails(idx) => {
-                if let Some(trace) = self.traces.lock().unwrap().get(*idx).cloned() {
-                    (Some(*idx), waterfall::Waterfall::new(trace).draw(ui))
+            Tab::TraceDetails(id) => {
+                if let Some(trace) = self.snapshot(id) {
+                    let state = self.waterfalls.entry(id.clone()).or_default();
+                    (
+                        Some(id.clone()),
+                        waterfall::Waterfall::new(&trace, state).draw(ui),
+                    )
+                } else {
+                    (None, None)
+                }
+            }
+            Tab::TraceDiff(left_id, right_id) => {
+                if let (Some(left), Some(right)) = (self.snapshot(left_id), self.snapshot(right_id))
+                {
+                    let state = self
+                        .diffs
+                        .entry((left_id.clone(), right_id.clone()))
+                        .or_default();
+                    (None, diff::Diff::new(&left, &right, state).draw(ui))
                 } else {
                     (None, None)
                 }
@@ -111,15 +366,33 @@ impl egui_dock::TabViewer for TabViewer {
         };
         if let Some(action) = action {
             let tab = match action {
-                Action::OpenSpanAttributes(span_idx) => {
-                    if let Some(trace_idx) = trace_idx {
-                        Some(Tab::SpanAttributes(trace_idx, span_idx))
+                Action::OpenSpanAttributes(span_id) => {
+                    if let Some(trace_id) = trace_id {
+                        Some(Tab::SpanAttributes(trace_id, span_id))
                     } else {
-                        error!("attempt to open span without trace index");
+                        error!("attempt to open span without trace ID");
                         None
                     }
                 }
-                Action::OpenTraceDetails(trace_idx) => Some(Tab::TraceDetails(trace_idx)),
+                Action::OpenTraceDetails(trace_id) => Some(Tab::TraceDetails(trace_id)),
+                Action::OpenTraces(trace_ids) => {
+                    self.last_frame_tabs
+                        .extend(trace_ids.into_iter().map(Tab::TraceDetails));
+                    None
+                }
+                Action::OpenTraceDiff(left_id, right_id) => Some(Tab::TraceDiff(left_id, right_id)),
+                Action::OpenAggregateFlameGraph(selection) => {
+                    self.aggregate.select(selection);
+                    Some(Tab::AggregateFlameGraph)
+                }
+                Action::FilterTracesByOperation { service, name } => {
+                    self.list.filter_by_operation(service, name);
+                    Some(Tab::TraceList)
+                }
+                Action::FilterTracesByServiceCall { caller, callee } => {
+                    self.list.filter_by_service_call(caller, callee);
+                    Some(Tab::TraceList)
+                }
             };
 
             if let Some(tab) = tab {
@@ -130,26 +403,18 @@ impl egui_dock::TabViewer for TabViewer {
 
     fn title(&mut self, tab: &mut Self::Tab) -> egui::WidgetText {
         let title: String = match tab {
+            Tab::AggregateFlameGraph => {
+                format!("Flame graph: {} traces", self.aggregate.trace_count())
+            }
             Tab::Appearance => "Appearance".into(),
             Tab::Collector => "Collector".into(),
-            Tab::SpanAttributes(trace_idx, span_idx) => format!(
-                "Span: {}",
-                self.traces
-                    .lock()
-                    .unwrap()
-                    .get(*trace_idx)
-                    .and_then(|trace| trace.spans.get(*span_idx))
-                    .map_or("<unknown>".to_string(), |span| span.id.clone())
-            ),
+            Tab::History => "History".into(),
+            Tab::ServiceGraph => "Services".into(),
+            Tab::SpanAttributes(_, span_id) => format!("Span: {span_id}"),
+            Tab::Statistics => "Statistics".into(),
             Tab::TraceList => "Traces".into(),
-            Tab::TraceDetails(idx) => format!(
-                "Trace: {}",
-                self.traces
-                    .lock()
-                    .unwrap()
-                    .get(*idx)
-                    .map_or("<unknown>".to_string(), |trace| trace.id.clone())
-            ),
+            Tab::TraceDiff(left_id, right_id) => format!("Diff: {left_id} → {right_id}"),
+            Tab::TraceDetails(id) => format!("Trace: {id}"),
         };
         title.into()
     }
@@ -159,6 +424,8 @@ pub struct App {
     /// User-actionable error message from most recent operation.
     error: Option<String>, // TODO: display this to users
     traces: Arc<Mutex<Vec<Trace>>>,
+    /// Saves imported spans, unless the store couldn't be opened.
+    writer: Option<StoreWriter>,
 
     viewer: TabViewer,
     tree: Tree<Tab>,
@@ -166,19 +433,67 @@ pub struct App {
 
 impl Default for App {
     fn default() -> Self {
+        Self::with_store(None)
+    }
+}
+
+impl App {
+    fn with_store(store: Option<SharedStore>) -> Self {
         let traces: Arc<Mutex<Vec<Trace>>> = Arc::default();
+        let writer = store.clone().map(StoreWriter::spawn);
         Self {
             error: Option::default(),
             traces: traces.clone(),
-            viewer: TabViewer::new(traces),
+            writer: writer.clone(),
+            viewer: TabViewer::new(traces, store, writer),
             tree: Tree::default(),
         }
     }
+
+    /// Restore what the previous session saved in
+    /// [`eframe::App::save`], and start indexing and loading the
+    /// traces it stored in the background.
+    #[must_use]
+    pub fn new(cc: &eframe::CreationContext<'_>) -> Self {
+        let log = open_store()
+            .map_err(|err| error!("opening trace store: {err}"))
+            .ok();
+        let mut app = Self::with_store(log.clone().map(|log| log as SharedStore));
+        if let Some(log) = log {
+            history::restore(log, app.traces.clone(), cc.egui_ctx.clone());
+        }
+        if let Some(saved_queries) = cc
+            .storage
+            .and_then(|storage| eframe::get_value(storage, list::SAVED_QUERIES_KEY))
+        {
+            app.viewer.list.set_saved_queries(saved_queries);
+        }
+        if let Some(layout) = cc
+            .storage
+            .and_then(|storage| eframe::get_value(storage, list::LAYOUT_KEY))
+        {
+            app.viewer.list.set_layout(layout);
+        }
+        app
+    }
 }
 
 impl eframe::App for App {
     fn update(&mut self, ctx: &eframe::egui::Context, frame: &mut eframe::Frame) {
         self.menu_bar(ctx, frame);
+        let open = self
+            .tree
+            .iter()
+            .filter_map(|node| match node {
+                egui_dock::Node::Leaf { tabs, .. } => Some(tabs),
+                _ => None,
+            })
+            .flatten()
+            .flat_map(Tab::trace_ids)
+            .cloned()
+            .collect();
+        self.viewer.enforce_retention(open);
+        self.viewer.enforce_storage();
 
         egui::CentralPanel::default().show(ctx, |ui| {
             if self.tree.is_empty() {
@@ -204,6 +519,18 @@ impl eframe::App for App {
         ctx.input(|i| {
             self.handle_input(i);
         });
+        if let Some(after) = self.viewer.refresh_after() {
+            ctx.request_repaint_after(after);
+        }
+    }
+
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        eframe::set_value(
+            storage,
+            list::SAVED_QUERIES_KEY,
+            self.viewer.list.saved_queries(),
+        );
+        eframe::set_value(storage, list::LAYOUT_KEY, self.viewer.list.layout());
     }
 }
 
@@ -233,6 +560,18 @@ impl App {
                         ui.close_menu();
                         self.add_tab(Tab::Collector);
                     }
+                    if ui.button("History").clicked() {
+                        ui.close_menu();
+                        self.add_tab(Tab::History);
+                    }
+                    if ui.button("Services").clicked() {
+                        ui.close_menu();
+                        self.add_tab(Tab::ServiceGraph);
+                    }
+                    if ui.button("Statistics").clicked() {
+                        ui.close_menu();
+                        self.add_tab(Tab::Statistics);
+                    }
                     if ui.button("Traces").clicked() {
                         ui.close_menu();
                         self.add_tab(Tab::TraceList);
@@ -260,17 +599,15 @@ impl App {
     /// [`Tab::SpanAttributes`] is always opened in a right-split.
     fn add_tab(&mut self, tab: Tab) {
         match tab {
-            Tab::SpanAttributes(trace_idx, span_idx) => {
+            Tab::SpanAttributes(ref trace_id, ref span_id) => {
                 if let Some((node_idx, tab_idx)) = self.tree.find_tab(&tab) {
                     self.tree.set_focused_node(node_idx);
                     self.tree.set_active_tab(node_idx, tab_idx);
-                    if let Some((
-                        _rect,
-                        Tab::SpanAttributes(existing_trace_idx, existing_span_idx),
-                    )) = self.tree.find_active_focused()
+                    if let Some((_rect, Tab::SpanAttributes(existing_trace_id, existing_span_id))) =
+                        self.tree.find_active_focused()
                     {
-                        *existing_trace_idx = trace_idx;
-                        *existing_span_idx = span_idx;
+                        existing_trace_id.clone_from(trace_id);
+                        existing_span_id.clone_from(span_id);
                     } else {
                         error!("found span attributes tab that can't be destructured");
                     }
@@ -320,9 +657,19 @@ impl App {
     }
 
     fn load_traces_from_file(&mut self, file_path: &Path) -> Result<(), String> {
-        let mut parsed_traces = parse_file(file_path).and_then(build_traces)?;
+        let spans = parse_file(file_path)?;
+        if let Some(writer) = &self.writer {
+            writer.save(spans.clone());
+        }
+        let mut parsed_traces = build_traces(spans)?;
         let mut traces = self.traces.lock().unwrap();
+        let present = traces
+            .iter()
+            .map(|trace| trace.id.clone())
+            .collect::<HashSet<_>>();
+        parsed_traces.retain(|trace| !present.contains(&trace.id));
         traces.append(&mut parsed_traces);
+        bump_generation();
 
         Ok(())
     }
@@ -338,25 +685,55 @@ impl App {
     }
 }
 
-/// Recalculate `traces` whenever new message arrives on `rx`. Only
-/// traces that were updated in the message _should_ be recalculated
-/// (not true right now).
+/// Open the [`SegmentLog`] in the platform's data directory, leaving
+/// it to [`history::restore`] to index.
+fn open_store() -> Result<Arc<Mutex<SegmentLog>>, String> {
+    let dirs = directories_next::ProjectDirs::from("", "", "egui-trace")
+        .ok_or("no home directory to store traces in")?;
+    let log = SegmentLog::open(&dirs.data_dir().join("spans"))?;
+    Ok(Arc::new(Mutex::new(log)))
+}
+
+/// Trace with ID `id`, wherever it currently is in `traces`.
+fn find_trace<'a>(traces: &'a [Trace], id: &str) -> Option<&'a Trace> {
+    traces.iter().find(|trace| trace.id == id)
+}
+
+/// Span with ID `span_id` in the trace with ID `trace_id`.
+fn find_span<'a>(traces: &'a [Trace], trace_id: &str, span_id: &str) -> Option<&'a Span> {
+    let trace = find_trace(traces, trace_id)?;
+    trace.span_index(span_id).map(|idx| &trace.spans[idx])
+}
+
+/// Bumped by every change to the traces, see [`generation`].
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// Counter bumped by every change to the traces, used to detect when
+/// derived views need to be recomputed. Read with the traces locked.
+fn generation() -> u64 {
+    GENERATION.load(Ordering::Relaxed)
+}
+
+/// Note a change to the traces, made with them locked.
+fn bump_generation() {
+    GENERATION.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Save spans with `writer` and recalculate `traces` whenever new message
+/// arrives on `rx`, unless `live` is paused, in which case the spans
+/// are buffered. Only traces that were updated in the message
+/// _should_ be recalculated (not true right now).
 async fn collect_spans_and_recalculate(
     mut rx: mpsc::Receiver<Vec<Span>>,
     traces: Arc<Mutex<Vec<Trace>>>,
+    live: Arc<Mutex<live::Live>>,
+    writer: Option<StoreWriter>,
 ) {
-    while let Some(mut spans) = rx.recv().await {
-        let mut traces = traces.lock().unwrap();
-        let mut all_spans = traces
-            .iter()
-            .flat_map(|trace| trace.spans.clone())
-            .collect::<Vec<_>>();
-        all_spans.append(&mut spans);
-
-        let res = build_traces(all_spans);
-        match res {
-            Ok(res) => (*traces) = res,
-            Err(msg) => error!("rebuilding traces on collector ingestions: {msg}"),
+    while let Some(spans) = rx.recv().await {
+        if let Some(writer) = &writer {
+            writer.save(spans.clone());
         }
+        let mut live = live.lock().unwrap();
+        live.receive(&mut traces.lock().unwrap(), spans);
     }
 }
@@ -1,145 +1,1174 @@
-use std::sync::{Arc, Mutex};
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, BTreeSet, HashSet},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
 
-use eframe::egui::Grid;
-use egui_extras::{Column as EguiColumn, TableBuilder};
-use lib::Trace;
+use eframe::egui::{
+    Button, CollapsingHeader, ComboBox, Grid, Modifiers, RichText, Sense, TextEdit,
+};
+use egui_extras::{Column as EguiColumn, TableBuilder, TableRow};
+use lib::{format_duration, query::Query, stats::Summary, Trace};
 
-#[derive(Debug, Default, PartialEq)]
+use crate::live::Live;
+
+/// Key under which the saved queries are persisted.
+pub(crate) const SAVED_QUERIES_KEY: &str = "trace_list_saved_queries";
+
+/// Key under which [`Layout`] is persisted.
+pub(crate) const LAYOUT_KEY: &str = "trace_list_layout";
+
+/// Number of attribute keys suggested when adding a column.
+const COLUMN_KEY_SUGGESTIONS: usize = 10;
+
+/// How often to repaint while newly arrived traces fade out.
+const HIGHLIGHT_REFRESH: Duration = Duration::from_millis(50);
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, serde::Deserialize, serde::Serialize)]
 enum Column {
     Id,
     Name,
     Duration,
     #[default]
     Start,
+    SpanCount,
+    ServiceCount,
+    ErrorCount,
+    /// `service.name` of the root span.
+    RootService,
+    /// Number of levels of spans.
+    Depth,
+    /// Attribute of the root span with this key.
+    Attribute(String),
+    /// Resource attribute of the root span with this key.
+    Resource(String),
+}
+
+/// Value of a [`Column`] for a trace, used for sorting.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum SortKey<'a> {
+    Missing,
+    Number(i64),
+    Time(chrono::DateTime<chrono::Utc>),
+    Text(&'a str),
+}
+
+impl Column {
+    /// Every column other than attribute columns.
+    const BUILT_IN: [Self; 9] = [
+        Self::Id,
+        Self::Name,
+        Self::Duration,
+        Self::Start,
+        Self::SpanCount,
+        Self::ServiceCount,
+        Self::ErrorCount,
+        Self::RootService,
+        Self::Depth,
+    ];
+
+    fn label(&self) -> String {
+        match self {
+            Self::Id => "Trace ID".to_string(),
+            Self::Name => "Name".to_string(),
+            Self::Duration => "Duration".to_string(),
+            Self::Start => "Start".to_string(),
+            Self::SpanCount => "Spans".to_string(),
+            Self::ServiceCount => "Services".to_string(),
+            Self::ErrorCount => "Errors".to_string(),
+            Self::RootService => "Service".to_string(),
+            Self::Depth => "Depth".to_string(),
+            Self::Attribute(key) => key.clone(),
+            Self::Resource(key) => format!("resource.{key}"),
+        }
+    }
+
+    /// Width the column starts at, and can't be shrunk below.
+    fn min_width(&self) -> f32 {
+        match self {
+            Self::Id => 250.0,
+            Self::Name | Self::Start => 150.0,
+            Self::Duration => 100.0,
+            Self::SpanCount | Self::ServiceCount | Self::ErrorCount | Self::Depth => 60.0,
+            Self::RootService | Self::Attribute(_) | Self::Resource(_) => 120.0,
+        }
+    }
+
+    fn sort_key<'a>(&self, trace: &'a Trace) -> SortKey<'a> {
+        let root = &trace.spans[0];
+        let count = |count: usize| SortKey::Number(i64::try_from(count).unwrap_or(i64::MAX));
+        // attributes are stored as text: sort numbers numerically
+        let text = |value: Option<&'a String>| {
+            value.map_or(SortKey::Missing, |value| {
+                value.parse().map_or(SortKey::Text(value), SortKey::Number)
+            })
+        };
+        match self {
+            Self::Id => SortKey::Text(&trace.id),
+            Self::Name => SortKey::Text(&root.name),
+            Self::Duration => SortKey::Number(root.duration_nanos),
+            Self::Start => SortKey::Time(root.start),
+            Self::SpanCount => count(trace.spans.len()),
+            Self::ServiceCount => count(service_count(trace)),
+            Self::ErrorCount => count(error_count(trace)),
+            Self::RootService => root.service_name().map_or(SortKey::Missing, SortKey::Text),
+            Self::Depth => count(depth(trace)),
+            Self::Attribute(key) => text(root.attributes.get(key)),
+            Self::Resource(key) => text(root.metadata.get(key)),
+        }
+    }
+
+    /// Text shown in the column's cell for `trace`.
+    fn text(&self, trace: &Trace) -> String {
+        let root = &trace.spans[0];
+        let or_dash = |value: Option<&String>| value.map_or("-".to_string(), Clone::clone);
+        match self {
+            Self::Id => trace.id.clone(),
+            Self::Name => root.name.clone(),
+            Self::Duration => format_duration(root.duration_nanos),
+            Self::Start => root.start.format("%b %e, %H:%M:%S%.3f").to_string(),
+            Self::SpanCount => trace.spans.len().to_string(),
+            Self::ServiceCount => service_count(trace).to_string(),
+            Self::ErrorCount => error_count(trace).to_string(),
+            Self::RootService => root.service_name().unwrap_or("-").to_string(),
+            Self::Depth => depth(trace).to_string(),
+            Self::Attribute(key) => or_dash(root.attributes.get(key)),
+            Self::Resource(key) => or_dash(root.metadata.get(key)),
+        }
+    }
+}
+
+fn service_count(trace: &Trace) -> usize {
+    trace
+        .spans
+        .iter()
+        .filter_map(lib::Span::service_name)
+        .collect::<HashSet<_>>()
+        .len()
+}
+
+fn error_count(trace: &Trace) -> usize {
+    trace.spans.iter().filter(|span| span.is_error()).count()
+}
+
+fn depth(trace: &Trace) -> usize {
+    trace
+        .spans
+        .iter()
+        .map(|span| span.level + 1)
+        .max()
+        .unwrap_or(0)
 }
 
-#[derive(Debug, Default, PartialEq)]
+#[derive(Debug, Clone, Default, PartialEq, serde::Deserialize, serde::Serialize)]
 enum Direction {
     #[default]
     Ascending,
     Descending,
 }
 
-#[derive(Debug, Default)]
-pub(crate) struct State {
-    search: String,
+/// [`crate::generation`] and filters [`State::matches`] was found
+/// with.
+type MatchKey = (
+    u64,
+    Query,
+    Option<(String, String)>,
+    Option<(String, String)>,
+);
+
+/// Columns shown and how rows are sorted. Persisted across sessions
+/// under [`LAYOUT_KEY`].
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+pub(crate) struct Layout {
+    columns: Vec<Column>,
     sort_column: Column,
     sort_direction: Direction,
+    /// Root span attribute or resource attribute to group rows by.
+    #[serde(default)]
+    group_by: Option<String>,
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Self {
+            columns: vec![Column::Id, Column::Name, Column::Duration, Column::Start],
+            sort_column: Column::default(),
+            sort_direction: Direction::default(),
+            group_by: None,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct State {
+    /// Text of the query box, see [`lib::query`].
+    query_text: String,
+    /// Last query that parsed, kept while the text has syntax errors
+    /// so the list doesn't change on every keystroke.
+    query: Query,
+    /// Syntax error in [`Self::query_text`], if any.
+    query_error: Option<String>,
+    /// Queries offered by the "Saved" dropdown. Persisted across
+    /// sessions under [`SAVED_QUERIES_KEY`].
+    saved_queries: Vec<String>,
+
+    layout: Layout,
+
+    /// Key typed into the "add attribute column" box.
+    new_column_key: String,
+
+    /// Only show traces containing a span with this
+    /// (`service.name`, span name).
+    operation: Option<(String, String)>,
+
+    /// Only show traces containing a call from the first service to
+    /// the second.
+    service_call: Option<(String, String)>,
+
+    /// Only show traces within the window brushed on the scatter
+    /// plot.
+    scatter: crate::scatter::State,
+
+    /// Traces selected for actions on several traces.
+    selection: Selection,
+
+    /// IDs of traces removed from the list with "Hide".
+    hidden: BTreeSet<String>,
+    /// IDs of traces chosen with "Delete", dropped from memory at the
+    /// start of the next frame.
+    deleted: BTreeSet<String>,
+    /// IDs of traces listed above the others, whatever the sort.
+    pinned: BTreeSet<String>,
+    /// Labels attached to traces with "Tag", by trace ID.
+    tags: BTreeMap<String, BTreeSet<String>>,
+    /// Tag typed into the "Tag" box.
+    new_tag: String,
+    /// Only show traces with this tag.
+    tag_filter: Option<String>,
+    /// Error from the last export, if it failed.
+    export_error: Option<String>,
+
+    /// Values of [`Layout::group_by`] whose traces are hidden.
+    collapsed_groups: HashSet<String>,
+
+    /// IDs of the traces matching [`Self::query`],
+    /// [`Self::operation`] and [`Self::service_call`]. These look
+    /// through every span, so are only searched for again when they or
+    /// the traces change.
+    matches: HashSet<String>,
+    matches_key: Option<MatchKey>,
+    /// Arrival of the newest trace the list has scrolled up to.
+    scrolled_to: Option<Instant>,
+}
+
+impl State {
+    /// Drop the traces with IDs in `deleted` from `traces`, along with
+    /// what is kept for them.
+    fn delete(&mut self, traces: &mut Vec<Trace>, deleted: &BTreeSet<String>) {
+        traces.retain(|trace| !deleted.contains(&trace.id));
+        crate::bump_generation();
+        for id in deleted {
+            self.hidden.remove(id);
+            self.pinned.remove(id);
+            self.tags.remove(id);
+        }
+    }
+
+    /// Bring [`Self::matches`] up to date with `traces` and the
+    /// filters.
+    fn update_matches(&mut self, traces: &[Trace]) {
+        let key = (
+            crate::generation(),
+            self.query.clone(),
+            self.operation.clone(),
+            self.service_call.clone(),
+        );
+        if self.matches_key.as_ref() == Some(&key) {
+            return;
+        }
+        self.matches = traces
+            .iter()
+            .filter(|trace| self.query.matches(trace))
+            .filter(|trace| match &self.operation {
+                Some((service, name)) => trace.spans.iter().any(|span| {
+                    &span.name == name && span.service_name().unwrap_or_default() == service
+                }),
+                None => true,
+            })
+            .filter(|trace| match &self.service_call {
+                Some((caller, callee)) => trace
+                    .service_calls()
+                    .iter()
+                    .any(|call| &call.caller == caller && &call.callee == callee),
+                None => true,
+            })
+            .map(|trace| trace.id.clone())
+            .collect();
+        self.matches_key = Some(key);
+    }
+}
+
+/// Traces selected in the list, by ID.
+#[derive(Debug, Default)]
+struct Selection {
+    traces: BTreeSet<String>,
+    /// Trace last clicked without shift, where shift-click ranges
+    /// start.
+    anchor: Option<String>,
+}
+
+impl Selection {
+    /// Update for a click on trace `id`. With shift, every trace
+    /// between the anchor and `id` in `order`, the traces as listed, is
+    /// added; with command, `id` is toggled; otherwise `id` becomes the
+    /// only selected trace.
+    fn click(&mut self, id: &str, modifiers: Modifiers, order: &[&str]) {
+        if modifiers.shift {
+            let position = |id: &str| order.iter().position(|&o| o == id);
+            if let Some((from, to)) = self.anchor.as_deref().and_then(position).zip(position(id)) {
+                let range = &order[from.min(to)..=from.max(to)];
+                self.traces.extend(range.iter().map(ToString::to_string));
+                return;
+            }
+        }
+        if modifiers.command {
+            if !self.traces.remove(id) {
+                self.traces.insert(id.to_string());
+            }
+        } else {
+            self.traces.clear();
+            self.traces.insert(id.to_string());
+        }
+        self.anchor = Some(id.to_string());
+    }
+
+    fn clear(&mut self) {
+        self.traces.clear();
+        self.anchor = None;
+    }
+}
+
+/// Traces sharing a value of [`Layout::group_by`].
+struct Group<'a> {
+    value: String,
+    traces: Vec<&'a Trace>,
+    /// Distribution of the traces' root span durations.
+    duration: Summary,
+}
+
+/// `traces` grouped by the value of `key` on their root span, largest
+/// group first. Traces keep their order within each group.
+fn group_by<'a>(traces: &[&'a Trace], key: &str) -> Vec<Group<'a>> {
+    let mut groups = BTreeMap::<&str, Vec<&Trace>>::new();
+    for &trace in traces {
+        let root = &trace.spans[0];
+        let value = root
+            .attributes
+            .get(key)
+            .or_else(|| root.metadata.get(key))
+            .map_or("-", String::as_str);
+        groups.entry(value).or_default().push(trace);
+    }
+    let mut groups = groups
+        .into_iter()
+        .map(|(value, traces)| {
+            let mut durations = traces
+                .iter()
+                .map(|trace| trace.spans[0].duration_nanos)
+                .collect::<Vec<_>>();
+            durations.sort_unstable();
+            Group {
+                value: value.to_string(),
+                traces,
+                duration: Summary::from_sorted(&durations),
+            }
+        })
+        .collect::<Vec<_>>();
+    // stable, so groups of the same size stay ordered by value
+    groups.sort_by_key(|group| std::cmp::Reverse(group.traces.len()));
+    groups
+}
+
+/// What each trace row shows, besides the trace itself.
+struct Rows<'a> {
+    columns: &'a [Column],
+    /// IDs of the listed traces, in the order shown.
+    order: &'a [&'a str],
+    pinned: &'a BTreeSet<String>,
+    tags: &'a BTreeMap<String, BTreeSet<String>>,
+    /// Highlights newly arrived traces.
+    live: &'a Live,
+}
+
+impl Rows<'_> {
+    /// Checkbox followed by a cell for each column. Clicking a row
+    /// selects it, with shift and command extending the selection.
+    /// Returns an action when the trace ID is clicked.
+    fn draw(
+        &self,
+        mut row: TableRow<'_, '_>,
+        selection: &mut Selection,
+        trace: &Trace,
+    ) -> Option<crate::Action> {
+        let highlight = self.live.highlight(&trace.id);
+        let mut click = None;
+        row.col(|ui| {
+            paint_highlight(ui, highlight);
+            let mut checked = selection.traces.contains(&trace.id);
+            if ui.checkbox(&mut checked, "").changed() {
+                // toggles, as a command-click would, unless extending
+                // a range
+                let modifiers = ui.input(|input| input.modifiers);
+                click = Some(Modifiers {
+                    command: !modifiers.shift,
+                    ..modifiers
+                });
+            }
+            if self.pinned.contains(&trace.id) {
+                ui.label("📌").on_hover_text("Pinned");
+            }
+        });
+        let mut action = None;
+        for column in self.columns {
+            let (_, response) = row.col(|ui| {
+                paint_highlight(ui, highlight);
+                if *column != Column::Id {
+                    ui.label(column.text(trace));
+                    return;
+                }
+                if ui.link(&trace.id).clicked() {
+                    let modifiers = ui.input(|input| input.modifiers);
+                    if modifiers.shift || modifiers.command {
+                        click = Some(modifiers);
+                    } else {
+                        action = Some(crate::Action::OpenTraceDetails(trace.id.clone()));
+                    }
+                }
+                for tag in self.tags.get(&trace.id).into_iter().flatten() {
+                    ui.label(RichText::new(format!("#{tag}")).small().weak());
+                }
+            });
+            if click.is_none() && action.is_none() {
+                let response = response.interact(Sense::click());
+                if response.clicked() {
+                    click = Some(response.ctx.input(|input| input.modifiers));
+                }
+            }
+        }
+        if let Some(modifiers) = click {
+            selection.click(&trace.id, modifiers, self.order);
+        }
+        action
+    }
+}
+
+/// Fill the cell behind its contents, fading as `highlight` drops
+/// from 1 to 0.
+fn paint_highlight(ui: &eframe::egui::Ui, highlight: Option<f32>) {
+    if let Some(highlight) = highlight {
+        let fill = ui
+            .visuals()
+            .selection
+            .bg_fill
+            .gamma_multiply(0.5 * highlight);
+        ui.painter().rect_filled(ui.max_rect(), 0.0, fill);
+    }
+}
+
+impl Layout {
+    /// Column heading, which sorts by the column when clicked, or
+    /// reverses the sort if it already does. Unless `sortable`, as
+    /// while the list is live and sorted newest first, the heading
+    /// is disabled and shows no sort.
+    fn draw_header(&mut self, ui: &mut eframe::egui::Ui, column: &Column, sortable: bool) {
+        let mut label = column.label();
+        if sortable && self.sort_column == *column {
+            label.push_str(match self.sort_direction {
+                Direction::Ascending => " ⏶",
+                Direction::Descending => " ⏷",
+            });
+        }
+        if ui
+            .add_enabled(
+                sortable,
+                Button::new(RichText::new(label).heading()).frame(false),
+            )
+            .on_hover_text("Sort by this column")
+            .on_disabled_hover_text("Pause to sort by this column")
+            .clicked()
+        {
+            if self.sort_column == *column {
+                self.sort_direction = match self.sort_direction {
+                    Direction::Ascending => Direction::Descending,
+                    Direction::Descending => Direction::Ascending,
+                };
+            } else {
+                self.sort_column = column.clone();
+                self.sort_direction = Direction::Ascending;
+            }
+        }
+    }
+}
+
+impl State {
+    /// Menu to show, hide and reorder columns, and to add columns for
+    /// root span attributes, suggesting keys found in `traces`.
+    fn draw_column_options(&mut self, ui: &mut eframe::egui::Ui, traces: &[&Trace]) {
+        let columns = &mut self.layout.columns;
+        let mut swap = None;
+        let mut remove = None;
+        Grid::new("list_columns").num_columns(2).show(ui, |ui| {
+            let count = columns.len();
+            for (n, column) in columns.iter().enumerate() {
+                ui.label(column.label());
+                ui.horizontal(|ui| {
+                    if ui.add_enabled(n > 0, Button::new("⏶").small()).clicked() {
+                        swap = Some((n - 1, n));
+                    }
+                    if ui
+                        .add_enabled(n + 1 < count, Button::new("⏷").small())
+                        .clicked()
+                    {
+                        swap = Some((n, n + 1));
+                    }
+                    if ui
+                        .add_enabled(count > 1, Button::new("✖").small())
+                        .clicked()
+                    {
+                        remove = Some(n);
+                    }
+                });
+                ui.end_row();
+            }
+        });
+        if let Some((l, r)) = swap {
+            columns.swap(l, r);
+        }
+        if let Some(n) = remove {
+            columns.remove(n);
+        }
+
+        ui.separator();
+        for column in Column::BUILT_IN {
+            if !columns.contains(&column) && ui.button(format!("Add {}", column.label())).clicked()
+            {
+                columns.push(column);
+            }
+        }
+
+        ui.separator();
+        ui.label("Root span attribute");
+        ui.text_edit_singleline(&mut self.new_column_key);
+        let key = self.new_column_key.trim().to_string();
+        ui.horizontal(|ui| {
+            for (column, label) in [
+                (Column::Attribute(key.clone()), "Add attribute"),
+                (Column::Resource(key.clone()), "Add resource attribute"),
+            ] {
+                let enabled = !key.is_empty() && !columns.contains(&column);
+                if ui.add_enabled(enabled, Button::new(label)).clicked() {
+                    columns.push(column);
+                }
+            }
+        });
+        let keys = traces
+            .iter()
+            .flat_map(|trace| trace.spans[0].attributes.keys())
+            .filter(|k| k.contains(key.as_str()))
+            .collect::<BTreeSet<_>>();
+        ui.horizontal_wrapped(|ui| {
+            for suggestion in keys.into_iter().take(COLUMN_KEY_SUGGESTIONS) {
+                if ui.small_button(suggestion).clicked() {
+                    self.new_column_key = suggestion.clone();
+                }
+            }
+        });
+
+        if !columns.contains(&self.layout.sort_column) {
+            self.layout.sort_column = columns[0].clone();
+        }
+    }
+
+    /// Replace the query text, parsing it if it is valid.
+    fn set_query(&mut self, text: String) {
+        match Query::parse(&text) {
+            Ok(query) => {
+                self.query = query;
+                self.query_error = None;
+            }
+            Err(err) => self.query_error = Some(err),
+        }
+        self.query_text = text;
+    }
 }
 
 pub(crate) struct TraceList {
     state: State,
     traces: Arc<Mutex<Vec<Trace>>>,
+    /// Shared with [`crate::collector::Collector`], which buffers
+    /// spans in it while the list is paused.
+    live: Arc<Mutex<Live>>,
 }
 
 impl TraceList {
-    pub(crate) fn new(traces: Arc<Mutex<Vec<Trace>>>) -> Self {
+    pub(crate) fn new(traces: Arc<Mutex<Vec<Trace>>>, live: Arc<Mutex<Live>>) -> Self {
         Self {
-            state: State::default(),
+            state: State {
+                saved_queries: vec!["has(error)".to_string(), "trace.duration > 1s".to_string()],
+                ..State::default()
+            },
             traces,
+            live,
         }
     }
+
+    pub(crate) fn saved_queries(&self) -> &Vec<String> {
+        &self.state.saved_queries
+    }
+
+    /// Use `saved_queries`, such as ones restored from storage, in
+    /// place of the examples offered on first run.
+    pub(crate) fn set_saved_queries(&mut self, saved_queries: Vec<String>) {
+        self.state.saved_queries = saved_queries;
+    }
+
+    pub(crate) fn layout(&self) -> &Layout {
+        &self.state.layout
+    }
+
+    /// Use `layout`, such as one restored from storage, unless it
+    /// sorts by a column it doesn't show (including when it shows no
+    /// columns at all), in which case the default is used instead.
+    pub(crate) fn set_layout(&mut self, layout: Layout) {
+        self.state.layout = if layout.columns.contains(&layout.sort_column) {
+            layout
+        } else {
+            Layout::default()
+        };
+    }
+
+    /// IDs of the pinned traces.
+    pub(crate) fn pinned(&self) -> &BTreeSet<String> {
+        &self.state.pinned
+    }
+
+    /// Restrict list to traces containing at least one span of the
+    /// given operation.
+    pub(crate) fn filter_by_operation(&mut self, service: String, name: String) {
+        self.state.operation = Some((service, name));
+    }
+
+    /// Restrict list to traces containing a call from `caller` to
+    /// `callee`.
+    pub(crate) fn filter_by_service_call(&mut self, caller: String, callee: String) {
+        self.state.service_call = Some((caller, callee));
+    }
 }
 
 impl crate::Panel for TraceList {
     fn draw(&mut self, ui: &mut eframe::egui::Ui) -> Option<crate::Action> {
-        // index in original slice is kept to use ensure the correct trace
-        // is reported as selected, since caller doesn't know we are
-        // filtering
-        let traces = self.traces.lock().unwrap();
-        let mut visible_traces = traces
+        let mut live_guard = self.live.lock().unwrap();
+        let mut traces_guard = self.traces.lock().unwrap();
+        if !self.state.deleted.is_empty() {
+            let deleted = std::mem::take(&mut self.state.deleted);
+            self.state.delete(&mut traces_guard, &deleted);
+        }
+        ui.horizontal(|ui| {
+            if live_guard.is_paused() {
+                if ui
+                    .button("▶ Resume")
+                    .on_hover_text("Add the traces received while paused")
+                    .clicked()
+                {
+                    live_guard.resume(&mut traces_guard);
+                }
+                let count = live_guard.new_trace_count(&traces_guard);
+                if count > 0 {
+                    ui.colored_label(ui.visuals().warn_fg_color, format!("{count} new traces"));
+                }
+            } else {
+                if ui
+                    .button("⏸ Pause")
+                    .on_hover_text("Hold the list still, keeping traces that arrive for later")
+                    .clicked()
+                {
+                    live_guard.pause();
+                }
+                ui.weak("Live, newest first");
+            }
+        });
+        self.state.update_matches(&traces_guard);
+        let (live, traces) = (&*live_guard, &*traces_guard);
+
+        let filtered_traces = traces
             .iter()
-            .enumerate()
-            .filter(|(_, trace)| {
-                let search = self.state.search.as_str();
-                trace.spans[0].name.starts_with(search) || trace.id.starts_with(search)
+            .filter(|trace| !self.state.hidden.contains(&trace.id))
+            .filter(|trace| match &self.state.tag_filter {
+                Some(tag) => self
+                    .state
+                    .tags
+                    .get(&trace.id)
+                    .is_some_and(|tags| tags.contains(tag)),
+                None => true,
             })
-            .collect::<Vec<(usize, &Trace)>>();
-        match self.state.sort_column {
-            Column::Id => visible_traces.sort_by_key(|(_, trace)| &trace.id),
-            Column::Name => visible_traces.sort_by_key(|(_, trace)| &trace.spans[0].name),
-            Column::Duration => {
-                visible_traces.sort_by_key(|(_, trace)| trace.spans[0].duration_micros);
+            .filter(|trace| self.state.matches.contains(&trace.id))
+            .collect::<Vec<&Trace>>();
+        // the scatter plot shows traces outside the brush too, so it
+        // is applied separately
+        let mut visible_traces = filtered_traces
+            .iter()
+            .copied()
+            .filter(|trace| {
+                self.state
+                    .scatter
+                    .brush
+                    .is_none_or(|brush| brush.contains(trace))
+            })
+            .collect::<Vec<&Trace>>();
+        if live.is_paused() {
+            let sort_column = &self.state.layout.sort_column;
+            visible_traces.sort_by_cached_key(|trace| sort_column.sort_key(trace));
+            if self.state.layout.sort_direction == Direction::Descending {
+                visible_traces.reverse();
             }
-            Column::Start => visible_traces.sort_by_key(|(_, trace)| trace.spans[0].start),
-        }
-        if self.state.sort_direction == Direction::Descending {
-            visible_traces.reverse();
+        } else {
+            visible_traces.sort_by_key(|trace| Reverse(trace.spans[0].start));
         }
+        // stable, so pinned traces keep the sort order among themselves
+        visible_traces.sort_by_key(|trace| !self.state.pinned.contains(&trace.id));
 
+        let mut query_text = None;
         ui.collapsing("Filters", |ui| {
             Grid::new("list_filters").num_columns(2).show(ui, |ui| {
-                ui.label("Search");
-                ui.text_edit_singleline(&mut self.state.search);
-                ui.end_row();
-
-                ui.label("Sort");
+                ui.label("Query");
                 ui.horizontal(|ui| {
-                    ui.radio_value(&mut self.state.sort_column, Column::Id, "Trace ID");
-                    ui.radio_value(&mut self.state.sort_column, Column::Name, "Name");
-                    ui.radio_value(&mut self.state.sort_column, Column::Duration, "Duration");
-                    ui.radio_value(&mut self.state.sort_column, Column::Start, "Start Time");
+                    let mut text = self.state.query_text.clone();
+                    if ui
+                        .add(
+                            TextEdit::singleline(&mut text)
+                                .hint_text(r#"service.name = "checkout" AND duration > 200ms"#)
+                                .desired_width(400.0),
+                        )
+                        .changed()
+                    {
+                        query_text = Some(text);
+                    }
+                    ComboBox::from_id_source("list_saved_queries")
+                        .selected_text("Saved")
+                        .show_ui(ui, |ui| {
+                            for saved in &self.state.saved_queries {
+                                if ui
+                                    .selectable_label(*saved == self.state.query_text, saved)
+                                    .clicked()
+                                {
+                                    query_text = Some(saved.clone());
+                                }
+                            }
+                        });
+                    let text = self.state.query_text.trim();
+                    if let Some(saved) = self.state.saved_queries.iter().position(|q| q == text) {
+                        if ui.button("Forget").clicked() {
+                            self.state.saved_queries.remove(saved);
+                        }
+                    } else if ui
+                        .add_enabled(
+                            !text.is_empty() && self.state.query_error.is_none(),
+                            Button::new("Save"),
+                        )
+                        .clicked()
+                    {
+                        self.state.saved_queries.push(text.to_string());
+                    }
                 });
                 ui.end_row();
 
-                ui.label("");
-                ui.horizontal(|ui| {
-                    ui.radio_value(&mut self.state.sort_direction, Direction::Ascending, "asc");
-                    ui.radio_value(
-                        &mut self.state.sort_direction,
-                        Direction::Descending,
-                        "desc",
-                    );
-                });
-                ui.end_row();
+                if let Some(err) = &self.state.query_error {
+                    ui.label("");
+                    ui.colored_label(ui.visuals().error_fg_color, err);
+                    ui.end_row();
+                }
+
+                if let Some((service, name)) = &self.state.operation {
+                    ui.label("Operation");
+                    let mut clear = false;
+                    ui.horizontal(|ui| {
+                        ui.label(format!("{service} {name}"));
+                        clear = ui.small_button("✖").clicked();
+                    });
+                    if clear {
+                        self.state.operation = None;
+                    }
+                    ui.end_row();
+                }
+
+                if let Some((caller, callee)) = &self.state.service_call {
+                    ui.label("Call");
+                    let mut clear = false;
+                    ui.horizontal(|ui| {
+                        ui.label(format!("{caller} → {callee}"));
+                        clear = ui.small_button("✖").clicked();
+                    });
+                    if clear {
+                        self.state.service_call = None;
+                    }
+                    ui.end_row();
+                }
+
+                if let Some(tag) = &self.state.tag_filter {
+                    ui.label("Tag");
+                    let mut clear = false;
+                    ui.horizontal(|ui| {
+                        ui.label(format!("#{tag}"));
+                        clear = ui.small_button("✖").clicked();
+                    });
+                    if clear {
+                        self.state.tag_filter = None;
+                    }
+                    ui.end_row();
+                }
+
+                if !self.state.hidden.is_empty() {
+                    ui.label("Hidden");
+                    ui.horizontal(|ui| {
+                        ui.label(format!("{} traces", self.state.hidden.len()));
+                        if ui.small_button("Show all").clicked() {
+                            self.state.hidden.clear();
+                        }
+                    });
+                    ui.end_row();
+                }
+
+                if let Some(brush) = &self.state.scatter.brush {
+                    ui.label("Brush");
+                    let mut clear = false;
+                    ui.horizontal(|ui| {
+                        ui.label(brush.label());
+                        clear = ui.small_button("✖").clicked();
+                    });
+                    if clear {
+                        self.state.scatter.brush = None;
+                    }
+                    ui.end_row();
+                }
             });
         });
+        if let Some(text) = query_text {
+            self.state.set_query(text);
+        }
+        CollapsingHeader::new("Duration over time")
+            .default_open(true)
+            .show(ui, |ui| {
+                crate::scatter::ScatterPlot::new(&filtered_traces, &mut self.state.scatter)
+                    .show(ui);
+                ui.weak("Drag to filter by time and duration, click to clear.");
+            });
         ui.add_space(5.0);
 
         let mut action = None;
-        TableBuilder::new(ui)
-            .column(EguiColumn::auto().at_least(250.0))
-            .column(EguiColumn::auto().at_least(150.0))
-            .column(EguiColumn::auto().at_least(100.0))
-            .column(EguiColumn::remainder())
-            .striped(true)
-            .header(20.0, |mut header| {
-                header.col(|ui| {
-                    ui.heading("Trace ID");
-                });
-                header.col(|ui| {
-                    ui.heading("Name");
-                });
-                header.col(|ui| {
-                    ui.heading("Duration");
+        // spans to save, once the traces are unlocked
+        let mut export = None;
+        let selected = self.state.selection.traces.clone();
+        ui.horizontal(|ui| {
+            ui.label(format!("{} selected", selected.len()));
+            if ui.button("Select all").clicked() {
+                self.state
+                    .selection
+                    .traces
+                    .extend(visible_traces.iter().map(|trace| trace.id.clone()));
+            }
+            if ui
+                .add_enabled(!selected.is_empty(), Button::new("Clear"))
+                .clicked()
+            {
+                self.state.selection.clear();
+            }
+            ui.separator();
+            if ui
+                .add_enabled(!selected.is_empty(), Button::new("Open"))
+                .on_hover_text("Open each selected trace in its own tab")
+                .clicked()
+            {
+                action = Some(crate::Action::OpenTraces(
+                    selected.iter().cloned().collect(),
+                ));
+            }
+            if ui
+                .add_enabled(!selected.is_empty(), Button::new("Flame graph"))
+                .on_hover_text("Merge the selected traces into one flame graph")
+                .clicked()
+            {
+                action = Some(crate::Action::OpenAggregateFlameGraph(
+                    selected.iter().cloned().collect(),
+                ));
+            }
+            if ui
+                .add_enabled(selected.len() == 2, Button::new("Compare"))
+                .on_hover_text("Diff the slower of two selected traces against the faster")
+                .clicked()
+            {
+                // faster trace is the baseline, so slowdowns read as
+                // positive changes
+                let mut pair = selected
+                    .iter()
+                    .filter_map(|id| crate::find_trace(traces, id))
+                    .collect::<Vec<_>>();
+                pair.sort_by_key(|trace| trace.spans[0].duration_nanos);
+                if let [faster, slower] = pair[..] {
+                    action = Some(crate::Action::OpenTraceDiff(
+                        faster.id.clone(),
+                        slower.id.clone(),
+                    ));
+                }
+            }
+            if ui
+                .add_enabled(!selected.is_empty(), Button::new("Export"))
+                .on_hover_text("Save the selected traces' spans to a file")
+                .clicked()
+            {
+                export = Some(
+                    selected
+                        .iter()
+                        .filter_map(|id| crate::find_trace(traces, id))
+                        .flat_map(|trace| trace.spans.clone())
+                        .collect::<Vec<_>>(),
+                );
+            }
+            ui.separator();
+            let all_pinned = selected.iter().all(|i| self.state.pinned.contains(i));
+            if ui
+                .add_enabled(
+                    !selected.is_empty(),
+                    Button::new(if all_pinned { "Unpin" } else { "Pin" }),
+                )
+                .on_hover_text("Keep the selected traces at the top of the list")
+                .clicked()
+            {
+                for id in &selected {
+                    if all_pinned {
+                        self.state.pinned.remove(id);
+                    } else {
+                        self.state.pinned.insert(id.clone());
+                    }
+                }
+            }
+            if ui
+                .add_enabled(!selected.is_empty(), Button::new("Hide"))
+                .on_hover_text("Remove the selected traces from the list")
+                .clicked()
+            {
+                self.state.hidden.extend(selected.iter().cloned());
+                self.state.selection.clear();
+            }
+            if ui
+                .add_enabled(!selected.is_empty(), Button::new("Delete"))
+                .on_hover_text(
+                    "Drop the selected traces from memory. Stored traces stay in History.",
+                )
+                .clicked()
+            {
+                self.state.deleted.extend(selected.iter().cloned());
+                self.state.selection.clear();
+            }
+            ui.add(
+                TextEdit::singleline(&mut self.state.new_tag)
+                    .hint_text("tag")
+                    .desired_width(80.0),
+            );
+            let tag = self.state.new_tag.trim();
+            if ui
+                .add_enabled(!selected.is_empty() && !tag.is_empty(), Button::new("Tag"))
+                .on_hover_text("Label the selected traces")
+                .clicked()
+            {
+                for id in &selected {
+                    self.state
+                        .tags
+                        .entry(id.clone())
+                        .or_default()
+                        .insert(tag.to_string());
+                }
+                self.state.new_tag.clear();
+            }
+            let tags = self.state.tags.values().flatten().collect::<BTreeSet<_>>();
+            if !tags.is_empty() {
+                let mut tag_filter = self.state.tag_filter.clone();
+                ComboBox::from_id_source("list_tag_filter")
+                    .selected_text(
+                        tag_filter
+                            .as_deref()
+                            .map_or("All tags".to_string(), |tag| format!("#{tag}")),
+                    )
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut tag_filter, None, "All tags");
+                        for tag in tags {
+                            ui.selectable_value(
+                                &mut tag_filter,
+                                Some(tag.clone()),
+                                format!("#{tag}"),
+                            );
+                        }
+                    });
+                self.state.tag_filter = tag_filter;
+            }
+            ui.separator();
+            ui.menu_button("Columns", |ui| {
+                self.state.draw_column_options(ui, &visible_traces);
+            });
+            ui.label("Group by");
+            let mut group_by = self.state.layout.group_by.clone();
+            ComboBox::from_id_source("list_group_by")
+                .selected_text(group_by.as_deref().unwrap_or("None"))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut group_by, None, "None");
+                    let keys = visible_traces
+                        .iter()
+                        .flat_map(|trace| {
+                            let root = &trace.spans[0];
+                            root.attributes.keys().chain(root.metadata.keys())
+                        })
+                        .collect::<BTreeSet<_>>();
+                    for key in keys {
+                        ui.selectable_value(&mut group_by, Some(key.clone()), key);
+                    }
                 });
-                header.col(|ui| {
-                    ui.heading("Start");
+            if group_by != self.state.layout.group_by {
+                self.state.layout.group_by = group_by;
+                self.state.collapsed_groups.clear();
+            }
+        });
+        if let Some(err) = &self.state.export_error {
+            ui.colored_label(ui.visuals().error_fg_color, format!("Export failed: {err}"));
+        }
+        ui.add_space(5.0);
+
+        let groups = self
+            .state
+            .layout
+            .group_by
+            .as_deref()
+            .map(|key| group_by(&visible_traces, key));
+        let order = match &groups {
+            Some(groups) => groups
+                .iter()
+                .filter(|group| !self.state.collapsed_groups.contains(&group.value))
+                .flat_map(|group| group.traces.iter().map(|trace| trace.id.as_str()))
+                .collect::<Vec<_>>(),
+            None => visible_traces
+                .iter()
+                .map(|trace| trace.id.as_str())
+                .collect(),
+        };
+        let columns = self.state.layout.columns.clone();
+        let rows = Rows {
+            columns: &columns,
+            order: &order,
+            pinned: &self.state.pinned,
+            tags: &self.state.tags,
+            live,
+        };
+        // follow new traces to the top while live
+        let scroll_to_top = !live.is_paused() && live.last_arrival() > self.state.scrolled_to;
+        if scroll_to_top {
+            self.state.scrolled_to = live.last_arrival();
+        }
+        // widths are remembered per arrangement of columns
+        ui.push_id(&columns, |ui| {
+            let mut table = TableBuilder::new(ui)
+                .striped(true)
+                .resizable(true)
+                .column(EguiColumn::auto().resizable(false));
+            if scroll_to_top {
+                table = table.scroll_to_row(0, None);
+            }
+            for (n, column) in columns.iter().enumerate() {
+                table = table.column(if n + 1 == columns.len() {
+                    EguiColumn::remainder()
+                } else {
+                    EguiColumn::initial(column.min_width()).at_least(column.min_width())
                 });
-            })
-            .body(|mut body| {
-                for (i, trace) in &visible_traces {
-                    body.row(20.0, |mut row| {
-                        row.col(|ui| {
-                            if ui.link(&trace.id).clicked() {
-                                action = Some(crate::Action::OpenTraceDetails(*i));
-                            }
-                        });
-                        row.col(|ui| {
-                            ui.label(&trace.spans[0].name);
-                        });
-                        row.col(|ui| {
-                            ui.label(format!("{}ms", trace.spans[0].duration_micros / 1000));
+            }
+            table
+                .header(20.0, |mut header| {
+                    header.col(|_| {});
+                    for column in &columns {
+                        header.col(|ui| {
+                            self.state.layout.draw_header(ui, column, live.is_paused());
                         });
-                        row.col(|ui| {
-                            ui.label(format!(
-                                "{}",
-                                trace.spans[0].start.format("%b %e, %H:%M:%S%.3f")
-                            ));
+                    }
+                })
+                .body(|mut body| {
+                    let selection = &mut self.state.selection;
+                    let Some(groups) = &groups else {
+                        for &trace in &visible_traces {
+                            body.row(20.0, |row| {
+                                if let Some(clicked) = rows.draw(row, selection, trace) {
+                                    action = Some(clicked);
+                                }
+                            });
+                        }
+                        return;
+                    };
+                    for group in groups {
+                        let collapsed = self.state.collapsed_groups.contains(&group.value);
+                        body.row(20.0, |mut row| {
+                            row.col(|ui| {
+                                let icon = if collapsed { "⏵" } else { "⏷" };
+                                if ui.add(Button::new(icon).frame(false)).clicked() {
+                                    if collapsed {
+                                        self.state.collapsed_groups.remove(&group.value);
+                                    } else {
+                                        self.state.collapsed_groups.insert(group.value.clone());
+                                    }
+                                }
+                            });
+                            // spills over the empty cells after it
+                            row.col(|ui| {
+                                ui.strong(&group.value);
+                                ui.weak(format!(
+                                    "{} traces · p50 {} · p90 {} · p99 {}",
+                                    group.traces.len(),
+                                    format_duration(group.duration.p50),
+                                    format_duration(group.duration.p90),
+                                    format_duration(group.duration.p99),
+                                ));
+                            });
+                            for _ in 1..columns.len() {
+                                row.col(|_| {});
+                            }
                         });
-                    });
-                }
-            });
+                        if !collapsed {
+                            for &trace in &group.traces {
+                                body.row(20.0, |row| {
+                                    if let Some(clicked) = rows.draw(row, selection, trace) {
+                                        action = Some(clicked);
+                                    }
+                                });
+                            }
+                        }
+                    }
+                });
+        });
+        if let Some(spans) = export {
+            // the dialog blocks until closed, which mustn't hold up
+            // traces arriving
+            drop(traces_guard);
+            drop(live_guard);
+            if let Some(path) = rfd::FileDialog::new()
+                .set_file_name("traces.txt")
+                .save_file()
+            {
+                self.state.export_error = lib::write_file(&path, &spans).err();
+            }
+        }
         action
     }
+
+    fn refresh_after(&self) -> Option<Duration> {
+        self.live
+            .lock()
+            .unwrap()
+            .is_highlighting()
+            .then_some(HIGHLIGHT_REFRESH)
+    }
 }
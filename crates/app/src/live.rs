@@ -0,0 +1,179 @@
+use std::{
+    collections::{HashMap, HashSet},
+    time::{Duration, Instant},
+};
+
+use lib::{
+    build_traces,
+    retention::{span_bytes, Retention, Usage},
+    Span, Trace,
+};
+use tracing::error;
+
+/// How long traces stay highlighted after arriving.
+const HIGHLIGHT_DURATION: Duration = Duration::from_secs(3);
+
+/// Spans arriving from the collector, shared between it and the trace
+/// list. While paused, spans are buffered instead of being added to
+/// the traces, so the list holds still.
+///
+/// [`Self::retention`] is applied as spans are added, so memory stays
+/// bounded even when nothing is drawn. The buffer is held to its span
+/// and byte limits by dropping the earliest buffered spans.
+///
+/// Lock before [`crate::App`]'s traces when both are needed.
+#[derive(Debug, Default)]
+pub(crate) struct Live {
+    paused: bool,
+    /// Spans received while paused.
+    buffered: Vec<Span>,
+    /// [`span_bytes`] of [`Self::buffered`].
+    buffered_bytes: usize,
+    /// Limits applied to the traces and the buffer.
+    retention: Retention,
+    /// IDs of traces exempt from [`Self::retention`].
+    kept: HashSet<String>,
+    /// When each recently arrived trace first appeared.
+    arrivals: HashMap<String, Instant>,
+}
+
+impl Live {
+    pub(crate) fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub(crate) fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    /// Limits to apply from now on, and the traces exempt from them.
+    pub(crate) fn set_retention(&mut self, retention: Retention, kept: HashSet<String>) {
+        self.retention = retention;
+        self.kept = kept;
+    }
+
+    /// Exempt the trace with `id` from retention until the next
+    /// [`Self::set_retention`], such as while it is being opened.
+    pub(crate) fn keep(&mut self, id: &str) {
+        self.kept.insert(id.to_string());
+    }
+
+    /// Add the spans buffered while paused to `traces`, and add new
+    /// spans as they arrive again.
+    pub(crate) fn resume(&mut self, traces: &mut Vec<Trace>) {
+        self.paused = false;
+        let spans = std::mem::take(&mut self.buffered);
+        self.buffered_bytes = 0;
+        self.add(traces, spans);
+    }
+
+    /// Add `spans` from the collector to `traces`, or buffer them
+    /// while paused.
+    pub(crate) fn receive(&mut self, traces: &mut Vec<Trace>, mut spans: Vec<Span>) {
+        if self.paused {
+            self.buffered_bytes += spans.iter().map(span_bytes).sum::<usize>();
+            self.buffered.append(&mut spans);
+            self.trim_buffer();
+        } else {
+            self.add(traces, spans);
+        }
+    }
+
+    /// Drop the earliest buffered spans until the buffer is within
+    /// the span and byte limits.
+    fn trim_buffer(&mut self) {
+        let mut usage = Usage {
+            traces: 0,
+            spans: self.buffered.len(),
+            bytes: self.buffered_bytes,
+        };
+        let mut dropped = 0;
+        while dropped < self.buffered.len() && self.retention.is_exceeded(&usage) {
+            usage.spans -= 1;
+            usage.bytes -= span_bytes(&self.buffered[dropped]);
+            dropped += 1;
+        }
+        if dropped > 0 {
+            self.buffered.drain(..dropped);
+            self.buffered_bytes = usage.bytes;
+        }
+    }
+
+    /// Rebuild `traces` with `spans` added, keeping when known traces
+    /// arrived and noting which traces are new, then apply
+    /// [`Self::retention`].
+    fn add(&mut self, traces: &mut Vec<Trace>, mut spans: Vec<Span>) {
+        if spans.is_empty() {
+            return;
+        }
+        let known = traces
+            .iter()
+            .map(|trace| (trace.id.clone(), trace.arrived))
+            .collect::<HashMap<_, _>>();
+        let mut all_spans = traces
+            .iter()
+            .flat_map(|trace| trace.spans.clone())
+            .collect::<Vec<_>>();
+        all_spans.append(&mut spans);
+
+        match build_traces(all_spans) {
+            Ok(rebuilt) => *traces = rebuilt,
+            Err(msg) => {
+                error!("rebuilding traces on collector ingestions: {msg}");
+                return;
+            }
+        }
+        for trace in traces.iter_mut() {
+            if let Some(arrived) = known.get(&trace.id) {
+                trace.arrived = *arrived;
+            }
+        }
+        crate::bump_generation();
+        self.retention.apply(traces, chrono::Utc::now(), |trace| {
+            self.kept.contains(&trace.id)
+        });
+        let now = Instant::now();
+        self.arrivals
+            .retain(|_, arrived| now.duration_since(*arrived) < HIGHLIGHT_DURATION);
+        for trace in traces.iter() {
+            if !known.contains_key(&trace.id) {
+                self.arrivals.insert(trace.id.clone(), now);
+            }
+        }
+    }
+
+    /// Number of traces among the buffered spans that aren't in
+    /// `traces` yet.
+    pub(crate) fn new_trace_count(&self, traces: &[Trace]) -> usize {
+        let known = traces
+            .iter()
+            .map(|trace| trace.id.as_str())
+            .collect::<HashSet<_>>();
+        self.buffered
+            .iter()
+            .map(|span| span.trace_id.as_str())
+            .filter(|id| !known.contains(id))
+            .collect::<HashSet<_>>()
+            .len()
+    }
+
+    /// How much of its highlight the trace with `id` has left, from 1
+    /// on arrival down to 0, if it arrived recently.
+    pub(crate) fn highlight(&self, id: &str) -> Option<f32> {
+        let elapsed = self.arrivals.get(id)?.elapsed();
+        (elapsed < HIGHLIGHT_DURATION)
+            .then(|| 1.0 - elapsed.as_secs_f32() / HIGHLIGHT_DURATION.as_secs_f32())
+    }
+
+    /// Any trace is still highlighted.
+    pub(crate) fn is_highlighting(&self) -> bool {
+        self.arrivals
+            .values()
+            .any(|arrived| arrived.elapsed() < HIGHLIGHT_DURATION)
+    }
+
+    /// When the most recent trace arrived.
+    pub(crate) fn last_arrival(&self) -> Option<Instant> {
+        self.arrivals.values().max().copied()
+    }
+}
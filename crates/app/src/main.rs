@@ -9,7 +9,7 @@ async fn main() -> std::io::Result<()> {
     eframe::run_native(
         "Tracing",
         options,
-        Box::new(|_cc| Box::<egui_trace::App>::default()),
+        Box::new(|cc| Box::new(egui_trace::App::new(cc))),
     )
     .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))
 }
@@ -0,0 +1,174 @@
+use eframe::egui::{
+    plot::{Plot, PlotPoint, Points, Polygon},
+    Color32, Ui,
+};
+use lib::{format_duration, Trace};
+
+use crate::colors;
+
+/// Height of the plot.
+const PLOT_HEIGHT: f32 = 160.0;
+
+/// Radius of each trace's point.
+const POINT_RADIUS: f32 = 3.0;
+
+/// Time and duration window selected by dragging across the plot.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Brush {
+    /// Root span start times, in seconds since the Unix epoch.
+    start: (f64, f64),
+    /// Root span durations, in milliseconds.
+    duration: (f64, f64),
+}
+
+impl Brush {
+    fn new(from: PlotPoint, to: PlotPoint) -> Self {
+        Self {
+            start: (from.x.min(to.x), from.x.max(to.x)),
+            duration: (from.y.min(to.y).max(0.0), from.y.max(to.y)),
+        }
+    }
+
+    pub(crate) fn contains(&self, trace: &Trace) -> bool {
+        let [x, y] = point(trace);
+        (self.start.0..=self.start.1).contains(&x)
+            && (self.duration.0..=self.duration.1).contains(&y)
+    }
+
+    /// Time and duration ranges, for display.
+    #[allow(clippy::cast_possible_truncation)]
+    pub(crate) fn label(&self) -> String {
+        let nanos = |millis: f64| (millis * 1e6) as i64;
+        format!(
+            "{} – {}, {} – {}",
+            wall_clock(self.start.0, "%H:%M:%S%.3f"),
+            wall_clock(self.start.1, "%H:%M:%S%.3f"),
+            format_duration(nanos(self.duration.0)),
+            format_duration(nanos(self.duration.1)),
+        )
+    }
+
+    fn outline(&self) -> Vec<[f64; 2]> {
+        vec![
+            [self.start.0, self.duration.0],
+            [self.start.1, self.duration.0],
+            [self.start.1, self.duration.1],
+            [self.start.0, self.duration.1],
+        ]
+    }
+}
+
+/// Brush over the scatter plot. Kept across frames by the list.
+#[derive(Debug, Default)]
+pub(crate) struct State {
+    pub(crate) brush: Option<Brush>,
+
+    /// Plot position where a brush drag began.
+    brush_start: Option<PlotPoint>,
+}
+
+/// Duration of each trace's root span over its start time, with
+/// failed traces in red. Dragging across the plot brushes a time and
+/// duration window; clicking clears it.
+pub(crate) struct ScatterPlot<'a> {
+    traces: &'a [&'a Trace],
+    state: &'a mut State,
+}
+
+impl<'a> ScatterPlot<'a> {
+    pub(crate) fn new(traces: &'a [&'a Trace], state: &'a mut State) -> Self {
+        Self { traces, state }
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    pub(crate) fn show(self, ui: &mut Ui) {
+        // [ok, failed] × [inside brush, outside brush]
+        let mut series: [[Vec<[f64; 2]>; 2]; 2] = Default::default();
+        for trace in self.traces {
+            let failed = trace.spans.iter().any(lib::Span::is_error);
+            let outside = self.state.brush.is_some_and(|brush| !brush.contains(trace));
+            series[usize::from(failed)][usize::from(outside)].push(point(trace));
+        }
+
+        let state = self.state;
+        Plot::new("trace_scatter")
+            .height(PLOT_HEIGHT)
+            .allow_drag(false)
+            .allow_boxed_zoom(false)
+            .include_y(0.0)
+            .x_axis_formatter(|x, _| wall_clock(x, "%H:%M:%S"))
+            .y_axis_formatter(|y, _| format_duration((y * 1e6) as i64))
+            .label_formatter(|name, point| {
+                format!(
+                    "{name}\n{}\n{}",
+                    wall_clock(point.x, "%H:%M:%S%.3f"),
+                    format_duration((point.y * 1e6) as i64)
+                )
+            })
+            .show(ui, |plot_ui| {
+                for (failed, [inside, outside]) in series.into_iter().enumerate() {
+                    let (color, name) = if failed == 1 {
+                        (colors::INDIAN_RED, "error")
+                    } else {
+                        (colors::CERULEAN, "ok")
+                    };
+                    for (points, color) in [(inside, color), (outside, color.gamma_multiply(0.3))] {
+                        plot_ui.points(
+                            Points::new(points)
+                                .color(color)
+                                .radius(POINT_RADIUS)
+                                .filled(true)
+                                .name(name),
+                        );
+                    }
+                }
+
+                let pointer = plot_ui.pointer_coordinate();
+                let (pressed, released) = plot_ui
+                    .ctx()
+                    .input(|i| (i.pointer.primary_pressed(), i.pointer.primary_released()));
+                if pressed && plot_ui.plot_hovered() {
+                    state.brush_start = pointer;
+                }
+                let dragging = match (state.brush_start, pointer) {
+                    (Some(start), Some(pointer)) => Some(Brush::new(start, pointer)).filter(|_| {
+                        let distance =
+                            plot_ui.screen_from_plot(start) - plot_ui.screen_from_plot(pointer);
+                        distance.x.abs() > 3.0 && distance.y.abs() > 3.0
+                    }),
+                    _ => None,
+                };
+                if released && state.brush_start.take().is_some() {
+                    // a click, rather than a drag, clears the brush
+                    state.brush = dragging;
+                }
+                if let Some(brush) = dragging.or(state.brush) {
+                    plot_ui.polygon(
+                        Polygon::new(brush.outline())
+                            .color(Color32::GRAY)
+                            .fill_alpha(0.15)
+                            .name("selection"),
+                    );
+                }
+            });
+    }
+}
+
+/// Position of `trace` on the plot: start time in seconds since the
+/// Unix epoch, and duration in milliseconds.
+#[allow(clippy::cast_precision_loss)]
+fn point(trace: &Trace) -> [f64; 2] {
+    let root = &trace.spans[0];
+    [
+        root.start.timestamp() as f64 + f64::from(root.start.timestamp_subsec_nanos()) / 1e9,
+        root.duration_nanos as f64 / 1e6,
+    ]
+}
+
+/// `seconds` since the Unix epoch formatted with `format`.
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn wall_clock(seconds: f64, format: &str) -> String {
+    let nanos = (seconds.fract() * 1e9) as u32;
+    chrono::DateTime::from_timestamp(seconds.floor() as i64, nanos)
+        .map_or_else(String::new, |time| time.format(format).to_string())
+}
@@ -0,0 +1,211 @@
+use std::{
+    collections::HashMap,
+    f32::consts::TAU,
+    sync::{Arc, Mutex},
+};
+
+use eframe::egui::{self, Align2, Color32, FontId, Pos2, Sense, Stroke, Vec2};
+use lib::{
+    format_duration,
+    service_graph::{service_graph, Edge, ServiceGraph as Graph},
+    Trace,
+};
+
+/// Radius of a service node in pixels.
+const NODE_RADIUS: f32 = 18.0;
+
+/// Maximum pointer distance, in pixels, for an edge to count as
+/// hovered.
+const EDGE_HOVER_DISTANCE: f32 = 6.0;
+
+/// Interactive node-link diagram of calls between services.
+pub(crate) struct ServiceGraph {
+    traces: Arc<Mutex<Vec<Trace>>>,
+
+    /// Last computed graph, along with the [`crate::generation`] it was
+    /// computed from.
+    graph: Graph,
+    cache_key: u64,
+
+    /// Node centers, normalized to the drawable area (0.0..=1.0).
+    /// Moved by dragging a node.
+    positions: HashMap<String, Vec2>,
+    /// Service whose node is currently being dragged.
+    dragging: Option<String>,
+}
+
+impl ServiceGraph {
+    pub(crate) fn new(traces: Arc<Mutex<Vec<Trace>>>) -> Self {
+        Self {
+            traces,
+            graph: Graph::default(),
+            cache_key: 0,
+            positions: HashMap::new(),
+            dragging: None,
+        }
+    }
+
+    /// Rebuild [`Self::graph`] if traces changed and lay out any new
+    /// services evenly around a circle.
+    fn refresh(&mut self) {
+        let traces = self.traces.lock().unwrap();
+        let key = crate::generation();
+        if key == self.cache_key {
+            return;
+        }
+        self.graph = service_graph(traces.iter());
+        self.cache_key = key;
+
+        #[allow(clippy::cast_precision_loss)]
+        let count = self.graph.services.len() as f32;
+        for (i, service) in self.graph.services.iter().enumerate() {
+            #[allow(clippy::cast_precision_loss)]
+            let angle = TAU * i as f32 / count;
+            self.positions
+                .entry(service.clone())
+                .or_insert_with(|| Vec2::new(0.5 + 0.4 * angle.cos(), 0.5 + 0.4 * angle.sin()));
+        }
+    }
+
+    /// Edge endpoints in screen space, trimmed to node boundaries and
+    /// nudged sideways so calls in both directions don't overlap.
+    fn edge_points(&self, edge: &Edge, to_screen: impl Fn(Vec2) -> Pos2) -> Option<(Pos2, Pos2)> {
+        let from = to_screen(*self.positions.get(&edge.caller)?);
+        let to = to_screen(*self.positions.get(&edge.callee)?);
+        let dir = (to - from).normalized();
+        let nudge = dir.rot90() * 4.0;
+        Some((
+            from + dir * NODE_RADIUS + nudge,
+            to - dir * NODE_RADIUS + nudge,
+        ))
+    }
+}
+
+impl crate::Panel for ServiceGraph {
+    fn draw(&mut self, ui: &mut egui::Ui) -> Option<crate::Action> {
+        self.refresh();
+
+        ui.label("Drag services to rearrange. Click a call to list the traces containing it.");
+        if self.graph.services.is_empty() {
+            ui.label("No spans report a service.name.");
+            return None;
+        }
+
+        let (response, painter) = ui.allocate_painter(ui.available_size(), Sense::click_and_drag());
+        let area = response.rect.shrink(NODE_RADIUS * 3.0);
+        let to_screen = |normalized: Vec2| area.min + normalized * area.size();
+        let pointer = response.hover_pos();
+
+        if response.drag_started() {
+            self.dragging = pointer.and_then(|pointer| {
+                self.graph
+                    .services
+                    .iter()
+                    .find(|service| {
+                        self.positions
+                            .get(*service)
+                            .is_some_and(|pos| to_screen(*pos).distance(pointer) <= NODE_RADIUS)
+                    })
+                    .cloned()
+            });
+        }
+        if response.dragged() {
+            if let Some(pos) = self
+                .dragging
+                .as_ref()
+                .and_then(|service| self.positions.get_mut(service))
+            {
+                *pos = (*pos + response.drag_delta() / area.size())
+                    .clamp(Vec2::ZERO, Vec2::splat(1.0));
+            }
+        }
+        if response.drag_released() {
+            self.dragging = None;
+        }
+
+        let hovered_edge = pointer
+            .filter(|_| self.dragging.is_none())
+            .and_then(|pointer| {
+                self.graph
+                    .edges
+                    .iter()
+                    .filter_map(|edge| {
+                        let (from, to) = self.edge_points(edge, to_screen)?;
+                        Some((edge, distance_to_segment(pointer, from, to)))
+                    })
+                    .filter(|(_, distance)| *distance <= EDGE_HOVER_DISTANCE)
+                    .min_by(|(_, l), (_, r)| l.total_cmp(r))
+                    .map(|(edge, _)| edge)
+            });
+
+        let visuals = ui.visuals();
+        let font = FontId::proportional(12.0);
+        for edge in &self.graph.edges {
+            let Some((from, to)) = self.edge_points(edge, to_screen) else {
+                continue;
+            };
+            let hovered = hovered_edge.is_some_and(|hovered| std::ptr::eq(hovered, edge));
+            let color = match (hovered, edge.errors > 0) {
+                (true, _) => visuals.strong_text_color(),
+                (false, true) => Color32::from_rgb(0xF2, 0x54, 0x5B),
+                (false, false) => visuals.weak_text_color(),
+            };
+            #[allow(clippy::cast_precision_loss)]
+            let width = 1.0 + (edge.count as f32).ln();
+            painter.arrow(from, to - from, Stroke::new(width, color));
+            painter.text(
+                from + (to - from) * 0.5,
+                Align2::CENTER_BOTTOM,
+                format!("{}× {}", edge.count, format_duration(edge.latency.avg)),
+                font.clone(),
+                color,
+            );
+        }
+
+        for service in &self.graph.services {
+            let Some(pos) = self.positions.get(service) else {
+                continue;
+            };
+            let center = to_screen(*pos);
+            painter.circle_filled(center, NODE_RADIUS, crate::colors::hashed(service));
+            painter.circle_stroke(center, NODE_RADIUS, visuals.widgets.inactive.fg_stroke);
+            painter.text(
+                center + Vec2::new(0.0, NODE_RADIUS + 2.0),
+                Align2::CENTER_TOP,
+                service,
+                font.clone(),
+                visuals.text_color(),
+            );
+        }
+
+        let mut action = None;
+        if let Some(edge) = hovered_edge {
+            egui::show_tooltip_at_pointer(ui.ctx(), ui.id().with("service_edge"), |ui| {
+                ui.strong(format!("{} → {}", edge.caller, edge.callee));
+                ui.label(format!("calls: {}", edge.count));
+                ui.label(format!("errors: {}", edge.errors));
+                ui.label(format!("avg: {}", format_duration(edge.latency.avg)));
+                ui.label(format!("p50: {}", format_duration(edge.latency.p50)));
+                ui.label(format!("p99: {}", format_duration(edge.latency.p99)));
+            });
+            if response.clicked() {
+                action = Some(crate::Action::FilterTracesByServiceCall {
+                    caller: edge.caller.clone(),
+                    callee: edge.callee.clone(),
+                });
+            }
+        }
+        action
+    }
+}
+
+/// Shortest distance from `point` to the segment between `a` and `b`.
+fn distance_to_segment(point: Pos2, a: Pos2, b: Pos2) -> f32 {
+    let segment = b - a;
+    let length_sq = segment.length_sq();
+    if length_sq == 0.0 {
+        return point.distance(a);
+    }
+    let t = ((point - a).dot(segment) / length_sq).clamp(0.0, 1.0);
+    point.distance(a + segment * t)
+}
@@ -1,12 +1,42 @@
-use eframe::egui::{ComboBox, Grid, Visuals};
+use eframe::egui::{ComboBox, DragValue, Grid, Ui, Visuals};
+use lib::retention::{Retention, Usage};
 
 // TODO: add custom colors to edit appearance screen
 // TODO: persist changes to appearance
 
+/// Bytes in a mebibyte, the unit memory limits are edited in.
+const MIB: usize = 1024 * 1024;
+
 /// User settings for application.
-#[derive(Debug, Default)]
+#[derive(Debug)]
 pub(crate) struct Settings {
     mode: Mode,
+    /// Limits on the traces kept in memory, enforced by
+    /// [`crate::TabViewer`].
+    pub(crate) retention: Retention,
+    /// Size of the traces in memory as of the last time
+    /// [`Self::retention`] was enforced.
+    pub(crate) usage: Usage,
+    /// Limits on the spans saved to disk, passed on to the store by
+    /// [`crate::TabViewer`].
+    pub(crate) storage: Retention,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            mode: Mode::default(),
+            retention: Retention {
+                max_bytes: Some(1024 * MIB),
+                ..Retention::default()
+            },
+            usage: Usage::default(),
+            storage: Retention {
+                max_bytes: Some(4096 * MIB),
+                ..Retention::default()
+            },
+        }
+    }
 }
 
 /// Panel to display persistent user settings.
@@ -38,10 +68,109 @@ impl<'a> crate::Panel for Panel<'a> {
                 });
             ui.end_row();
         });
+
+        ui.add_space(15.0);
+        ui.heading("Retention");
+        ui.label(
+            "When a limit is exceeded, the earliest received traces are dropped \
+             first. Pinned traces and traces open in tabs are never dropped.",
+        );
+        ui.add_space(5.0);
+        let usage = self.0.usage;
+        let retention = &mut self.0.retention;
+        Grid::new("settings_retention")
+            .num_columns(3)
+            .show(ui, |ui| {
+                ui.label("Traces");
+                ui.label(usage.traces.to_string());
+                limit(ui, &mut retention.max_traces, 10_000, 1, "");
+                ui.end_row();
+
+                ui.label("Spans");
+                ui.label(usage.spans.to_string());
+                limit(ui, &mut retention.max_spans, 1_000_000, 1, "");
+                ui.end_row();
+
+                ui.label("Memory");
+                ui.label(format!("~{}", format_bytes(usage.bytes)));
+                limit(ui, &mut retention.max_bytes, 1024 * MIB, MIB, " MiB");
+                ui.end_row();
+
+                ui.label("Age");
+                ui.label("");
+                age_limit(ui, &mut retention.max_age, 60);
+                ui.end_row();
+            });
+
+        ui.add_space(15.0);
+        ui.heading("Storage");
+        ui.label(
+            "Traces are also saved to disk, to be found in History. When a limit \
+             is exceeded, the earliest saved spans are deleted first.",
+        );
+        ui.add_space(5.0);
+        let storage = &mut self.0.storage;
+        Grid::new("settings_storage").num_columns(2).show(ui, |ui| {
+            ui.label("Spans");
+            limit(ui, &mut storage.max_spans, 10_000_000, 1, "");
+            ui.end_row();
+
+            ui.label("Disk");
+            limit(ui, &mut storage.max_bytes, 4096 * MIB, MIB, " MiB");
+            ui.end_row();
+
+            ui.label("Age");
+            age_limit(ui, &mut storage.max_age, 7 * 24 * 60);
+            ui.end_row();
+        });
         None
     }
 }
 
+/// [`limit`] editing `age` in minutes.
+fn age_limit(ui: &mut Ui, age: &mut Option<chrono::Duration>, default_minutes: usize) {
+    let mut minutes = age.map(|age| usize::try_from(age.num_minutes()).unwrap_or_default());
+    limit(ui, &mut minutes, default_minutes, 1, " min");
+    // chrono panics on durations over i64::MAX milliseconds
+    *age = minutes.map(|minutes| {
+        let minutes = i64::try_from(minutes).unwrap_or(i64::MAX);
+        chrono::Duration::minutes(minutes.min(i64::MAX / 60_000))
+    });
+}
+
+/// Checkbox enabling `value`, starting at `default`, followed by a
+/// field editing it in multiples of `unit`.
+fn limit(ui: &mut Ui, value: &mut Option<usize>, default: usize, unit: usize, suffix: &str) {
+    ui.horizontal(|ui| {
+        let mut enabled = value.is_some();
+        if ui.checkbox(&mut enabled, "Limit").changed() {
+            *value = enabled.then_some(default);
+        }
+        if let Some(value) = value {
+            let mut units = *value / unit;
+            ui.add(
+                DragValue::new(&mut units)
+                    .clamp_range(1..=usize::MAX / unit)
+                    .suffix(suffix),
+            );
+            *value = units * unit;
+        }
+    });
+}
+
+/// `bytes` in the largest binary unit that keeps the value at or
+/// above one.
+#[allow(clippy::cast_precision_loss)]
+fn format_bytes(bytes: usize) -> String {
+    let value = bytes as f64;
+    match bytes {
+        0..=1023 => format!("{bytes} B"),
+        1024..=0xF_FFFF => format!("{:.1} KiB", value / 1024.0),
+        0x10_0000..=0x3FFF_FFFF => format!("{:.1} MiB", value / 1024.0 / 1024.0),
+        _ => format!("{:.2} GiB", value / 1024.0 / 1024.0 / 1024.0),
+    }
+}
+
 /// Theme mode for entire application. Use [`System`] to default to
 /// system preference.
 #[derive(Debug, Default, PartialEq)]
@@ -0,0 +1,204 @@
+use std::{
+    cmp::Ordering,
+    sync::{Arc, Mutex},
+};
+
+use eframe::egui::{Grid, Ui};
+use egui_extras::{Column as EguiColumn, TableBuilder};
+use lib::{
+    format_duration,
+    stats::{operation_stats, OperationStats, Summary},
+    Trace,
+};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+enum Column {
+    Service,
+    Name,
+    #[default]
+    Count,
+    ErrorRate,
+    Min,
+    Avg,
+    P50,
+    P90,
+    P99,
+    Max,
+}
+
+/// Which distribution of [`OperationStats`] is shown in the table.
+#[derive(Debug, Default, PartialEq)]
+enum Metric {
+    #[default]
+    Duration,
+    SelfTime,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct State {
+    metric: Metric,
+    sort_column: Column,
+    sort_ascending: bool,
+}
+
+/// Per-operation statistics across every loaded [`Trace`].
+pub(crate) struct Statistics {
+    state: State,
+    traces: Arc<Mutex<Vec<Trace>>>,
+
+    /// Last computed stats, along with the [`crate::generation`] they
+    /// were computed from.
+    cache: Vec<OperationStats>,
+    cache_key: u64,
+}
+
+impl Statistics {
+    pub(crate) fn new(traces: Arc<Mutex<Vec<Trace>>>) -> Self {
+        Self {
+            state: State::default(),
+            traces,
+            cache: Vec::new(),
+            cache_key: 0,
+        }
+    }
+
+    /// Recompute [`Self::cache`] if traces were added or rebuilt
+    /// since the last frame.
+    fn refresh(&mut self) {
+        let traces = self.traces.lock().unwrap();
+        let key = crate::generation();
+        if key != self.cache_key {
+            self.cache = operation_stats(traces.iter());
+            self.cache_key = key;
+        }
+    }
+
+    fn summary<'a>(&self, stats: &'a OperationStats) -> &'a Summary {
+        match self.state.metric {
+            Metric::Duration => &stats.duration,
+            Metric::SelfTime => &stats.self_time,
+        }
+    }
+
+    fn compare(&self, l: &OperationStats, r: &OperationStats) -> Ordering {
+        let (ls, rs) = (self.summary(l), self.summary(r));
+        match self.state.sort_column {
+            Column::Service => l.service.cmp(&r.service),
+            Column::Name => l.name.cmp(&r.name),
+            Column::Count => l.count.cmp(&r.count),
+            Column::ErrorRate => l.error_rate().total_cmp(&r.error_rate()),
+            Column::Min => ls.min.cmp(&rs.min),
+            Column::Avg => ls.avg.cmp(&rs.avg),
+            Column::P50 => ls.p50.cmp(&rs.p50),
+            Column::P90 => ls.p90.cmp(&rs.p90),
+            Column::P99 => ls.p99.cmp(&rs.p99),
+            Column::Max => ls.max.cmp(&rs.max),
+        }
+    }
+
+    /// Clickable column header. Clicking the active column flips the
+    /// sort direction.
+    fn header(&mut self, ui: &mut Ui, column: Column, text: &str) {
+        let selected = self.state.sort_column == column;
+        let text = match (selected, self.state.sort_ascending) {
+            (true, true) => format!("{text} ⏶"),
+            (true, false) => format!("{text} ⏷"),
+            (false, _) => text.to_string(),
+        };
+        if ui.selectable_label(selected, text).clicked() {
+            if selected {
+                self.state.sort_ascending = !self.state.sort_ascending;
+            } else {
+                self.state.sort_column = column;
+                self.state.sort_ascending = false;
+            }
+        }
+    }
+}
+
+impl crate::Panel for Statistics {
+    fn draw(&mut self, ui: &mut eframe::egui::Ui) -> Option<crate::Action> {
+        self.refresh();
+
+        Grid::new("stats_options").num_columns(2).show(ui, |ui| {
+            ui.label("Metric");
+            ui.horizontal(|ui| {
+                ui.radio_value(&mut self.state.metric, Metric::Duration, "Duration");
+                ui.radio_value(&mut self.state.metric, Metric::SelfTime, "Self time");
+            });
+            ui.end_row();
+        });
+        ui.add_space(5.0);
+
+        let mut rows = (0..self.cache.len()).collect::<Vec<_>>();
+        rows.sort_by(|&l, &r| self.compare(&self.cache[l], &self.cache[r]));
+        if !self.state.sort_ascending {
+            rows.reverse();
+        }
+
+        let mut action = None;
+        TableBuilder::new(ui)
+            .column(EguiColumn::auto().at_least(120.0))
+            .column(EguiColumn::auto().at_least(200.0))
+            .columns(EguiColumn::auto().at_least(70.0), 8)
+            .striped(true)
+            .header(20.0, |mut header| {
+                for (column, text) in [
+                    (Column::Service, "Service"),
+                    (Column::Name, "Operation"),
+                    (Column::Count, "Count"),
+                    (Column::ErrorRate, "Errors"),
+                    (Column::Min, "Min"),
+                    (Column::Avg, "Avg"),
+                    (Column::P50, "p50"),
+                    (Column::P90, "p90"),
+                    (Column::P99, "p99"),
+                    (Column::Max, "Max"),
+                ] {
+                    header.col(|ui| self.header(ui, column, text));
+                }
+            })
+            .body(|mut body| {
+                for i in rows {
+                    let stats = &self.cache[i];
+                    let summary = self.summary(stats);
+                    body.row(20.0, |mut row| {
+                        row.col(|ui| {
+                            ui.label(if stats.service.is_empty() {
+                                "-"
+                            } else {
+                                &stats.service
+                            });
+                        });
+                        row.col(|ui| {
+                            if ui.link(&stats.name).clicked() {
+                                action = Some(crate::Action::FilterTracesByOperation {
+                                    service: stats.service.clone(),
+                                    name: stats.name.clone(),
+                                });
+                            }
+                        });
+                        row.col(|ui| {
+                            ui.label(stats.count.to_string());
+                        });
+                        row.col(|ui| {
+                            ui.label(format!("{:.1}%", stats.error_rate() * 100.0));
+                        });
+                        for value in [
+                            summary.min,
+                            summary.avg,
+                            summary.p50,
+                            summary.p90,
+                            summary.p99,
+                            summary.max,
+                        ] {
+                            row.col(|ui| {
+                                ui.label(format_duration(value));
+                            });
+                        }
+                    });
+                }
+            });
+        action
+    }
+}
@@ -1,87 +1,1258 @@
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    rc::Rc,
+};
+
+use crate::colors::ColorBy;
 use eframe::egui::{
-    Align, Color32, Frame, Grid, Layout, Response, Rounding, ScrollArea, Sense, Stroke, Style, Ui,
-    Vec2, Widget,
+    Align, Align2, Button, Color32, ComboBox, DragValue, FontId, Grid, Key, Layout, PointerButton,
+    Pos2, Rect, Response, Rounding, ScrollArea, Sense, Shape, Stroke, TextEdit, Ui, Vec2, Widget,
 };
-use lib::Trace;
+use lib::{critical_path::CriticalPath, flame_graph::FlameGraph, format_duration, Trace};
 
-pub(crate) struct Waterfall {
-    trace: Trace,
+/// How a trace details tab presents the trace.
+#[derive(Debug, Default, PartialEq)]
+enum View {
+    #[default]
+    Waterfall,
+    FlameGraph,
 }
 
-impl Waterfall {
-    pub(crate) fn new(trace: Trace) -> Self {
-        Self { trace }
+/// View options for a single trace details tab. Kept across frames
+/// by [`crate::TabViewer`].
+#[derive(Debug, Default)]
+pub(crate) struct State {
+    view: View,
+
+    /// What decides the color of each span's bar.
+    color_by: ColorBy,
+
+    /// Options for [`View::FlameGraph`].
+    flame_graph: crate::flame_graph::State,
+
+    /// Highlight spans on the critical path and dim everything else.
+    show_critical_path: bool,
+
+    /// Fade the portion of each bar spent waiting on children.
+    shade_self_time: bool,
+
+    /// IDs of spans whose descendants are hidden.
+    collapsed: HashSet<String>,
+
+    /// Depth used by "collapse below depth".
+    collapse_depth: usize,
+
+    /// Spans matching this (see [`lib::Span::matches_text`]) are
+    /// highlighted and everything else dimmed.
+    search: String,
+
+    /// ID of the match last navigated to with next/previous.
+    current_match: Option<String>,
+
+    /// Span to scroll into view on the next frame.
+    reveal: Option<usize>,
+
+    /// Visible time range, in nanosecond offsets from the root span.
+    /// `None` shows the root span's full duration.
+    window: Option<(i64, i64)>,
+
+    /// Screen x position where a drag-to-zoom selection began.
+    selection_start: Option<f32>,
+
+    /// Vertical scroll position, content height and visible height
+    /// of the waterfall as of the last frame. Used by the minimap.
+    scroll_offset: f32,
+    content_height: f32,
+    viewport_height: f32,
+
+    /// Vertical scroll offset to apply on the next frame.
+    scroll_to: Option<f32>,
+
+    /// Drag in progress on the minimap.
+    minimap_drag: Option<MinimapDrag>,
+
+    /// Values derived from the trace, kept across frames so drawing
+    /// doesn't walk every span.
+    cache: Cache,
+}
+
+/// Lazily computed values derived from a [`Trace`]. Cleared when the
+/// trace gains spans; [`Self::rows`] and [`Self::minimap`] are also
+/// cleared whenever spans are collapsed or expanded.
+#[derive(Debug, Default)]
+struct Cache {
+    /// Span count of the trace these values were computed from.
+    span_count: usize,
+    rows: Option<Rc<Vec<Row>>>,
+    critical_path: Option<Rc<CriticalPath>>,
+    flame_graph: Option<Rc<FlameGraph>>,
+    /// Color of every span, along with the strategy that chose them.
+    colors: Option<(ColorBy, Rc<Vec<Color32>>)>,
+    /// Legend for [`Self::colors`]: each key with its color and span
+    /// count, most common first.
+    legend: Option<Rc<Vec<(String, Color32, usize)>>>,
+    /// Every attribute key in the trace, sorted.
+    attribute_keys: Option<Rc<Vec<String>>>,
+    /// Indices of the spans with the most self time, descending.
+    top_self_time: Option<Vec<usize>>,
+    /// Minimap lines, along with the rect they were laid out in.
+    minimap: Option<(Rect, Vec<Shape>)>,
+    /// Indices of the spans matching a search, ascending, along with
+    /// the search they were matched against.
+    matches: Option<(String, Rc<Vec<usize>>)>,
+}
+
+/// A single visible row of the waterfall.
+#[derive(Debug, Clone, Copy)]
+struct Row {
+    /// Index into [`Trace::spans`].
+    span: usize,
+    /// Number of descendants hidden because the span is collapsed.
+    hidden: usize,
+}
+
+/// Kind of drag in progress on the minimap.
+#[derive(Debug, Clone, Copy)]
+enum MinimapDrag {
+    /// Moving the viewport outline.
+    Viewport,
+    /// Selecting a time range to zoom to, starting at the screen x.
+    Select(f32),
+}
+
+impl State {
+    /// Drop cached values if `trace` changed since they were computed.
+    fn sync_cache(&mut self, trace: &Trace) {
+        if self.cache.span_count != trace.spans.len() {
+            self.cache = Cache {
+                span_count: trace.spans.len(),
+                ..Cache::default()
+            };
+        }
+    }
+
+    /// Drop cached values that depend on which spans are collapsed.
+    fn invalidate_rows(&mut self) {
+        self.cache.rows = None;
+        self.cache.minimap = None;
+    }
+
+    /// Visible rows, skipping the descendants of collapsed spans.
+    fn rows(&mut self, trace: &Trace) -> Rc<Vec<Row>> {
+        self.sync_cache(trace);
+        let collapsed = &self.collapsed;
+        self.cache
+            .rows
+            .get_or_insert_with(|| {
+                let mut rows = Vec::new();
+                let mut i = 0;
+                while i < trace.spans.len() {
+                    let descendants = if collapsed.contains(&trace.spans[i].id) {
+                        trace.descendants(i)
+                    } else {
+                        i + 1..i + 1
+                    };
+                    rows.push(Row {
+                        span: i,
+                        hidden: descendants.len(),
+                    });
+                    i = descendants.end.max(i + 1);
+                }
+                Rc::new(rows)
+            })
+            .clone()
+    }
+
+    fn critical_path(&mut self, trace: &Trace) -> Rc<CriticalPath> {
+        self.sync_cache(trace);
+        self.cache
+            .critical_path
+            .get_or_insert_with(|| Rc::new(trace.critical_path()))
+            .clone()
+    }
+
+    /// Color of every span under [`Self::color_by`].
+    fn colors(&mut self, trace: &Trace) -> Rc<Vec<Color32>> {
+        self.sync_cache(trace);
+        match &self.cache.colors {
+            Some((color_by, colors)) if *color_by == self.color_by => colors.clone(),
+            _ => {
+                let colors = Rc::new(
+                    trace
+                        .spans
+                        .iter()
+                        .map(|span| self.color_by.color(span))
+                        .collect::<Vec<_>>(),
+                );
+                self.cache.colors = Some((self.color_by.clone(), colors.clone()));
+                self.cache.legend = None;
+                self.cache.minimap = None;
+                colors
+            }
+        }
+    }
+
+    fn legend(&mut self, trace: &Trace) -> Rc<Vec<(String, Color32, usize)>> {
+        let colors = self.colors(trace);
+        let color_by = &self.color_by;
+        self.cache
+            .legend
+            .get_or_insert_with(|| {
+                let mut entries = HashMap::<String, (Color32, usize)>::new();
+                for (span, color) in trace.spans.iter().zip(colors.iter()) {
+                    entries.entry(color_by.key(span)).or_insert((*color, 0)).1 += 1;
+                }
+                let mut legend = entries
+                    .into_iter()
+                    .map(|(key, (color, count))| (key, color, count))
+                    .collect::<Vec<_>>();
+                legend.sort_by(|l, r| r.2.cmp(&l.2).then_with(|| l.0.cmp(&r.0)));
+                Rc::new(legend)
+            })
+            .clone()
+    }
+
+    fn attribute_keys(&mut self, trace: &Trace) -> Rc<Vec<String>> {
+        self.sync_cache(trace);
+        self.cache
+            .attribute_keys
+            .get_or_insert_with(|| {
+                let keys = trace
+                    .spans
+                    .iter()
+                    .flat_map(|span| span.attributes.keys())
+                    .collect::<BTreeSet<_>>();
+                Rc::new(keys.into_iter().cloned().collect())
+            })
+            .clone()
+    }
+
+    /// Spans matching [`Self::search`]. Matches inside collapsed
+    /// subtrees stay hidden until navigated to.
+    fn matches(&mut self, trace: &Trace) -> Rc<Vec<usize>> {
+        self.sync_cache(trace);
+        match &self.cache.matches {
+            Some((search, matches)) if *search == self.search => matches.clone(),
+            _ => {
+                let matches = Rc::new(
+                    (0..trace.spans.len())
+                        .filter(|&i| trace.spans[i].matches_text(&self.search))
+                        .collect::<Vec<_>>(),
+                );
+                self.cache.matches = Some((self.search.clone(), matches.clone()));
+                matches
+            }
+        }
     }
+
+    /// Whether the span at `idx` matches the search, or `None` if
+    /// there is no search.
+    fn is_match(&self, idx: usize) -> Option<bool> {
+        if self.search.trim().is_empty() {
+            return None;
+        }
+        let matches = self.cache.matches.as_ref().map(|(_, matches)| matches);
+        Some(matches.is_some_and(|matches| matches.binary_search(&idx).is_ok()))
+    }
+
+    /// Move to the next (or previous) match after the current one,
+    /// wrapping around, and scroll it into view.
+    fn step_match(&mut self, trace: &Trace, forward: bool) {
+        let matches = self.matches(trace);
+        let (Some(&first), Some(&last)) = (matches.first(), matches.last()) else {
+            return;
+        };
+        let current = self
+            .current_match
+            .as_deref()
+            .and_then(|id| trace.span_index(id));
+        let next = match current {
+            Some(current) if forward => matches
+                .iter()
+                .copied()
+                .find(|&i| i > current)
+                .unwrap_or(first),
+            Some(current) => matches
+                .iter()
+                .rev()
+                .copied()
+                .find(|&i| i < current)
+                .unwrap_or(last),
+            None if forward => first,
+            None => last,
+        };
+        self.current_match = Some(trace.spans[next].id.clone());
+        self.expand_path_to(trace, next);
+        self.reveal = Some(next);
+    }
+
+    fn flame_graph(&mut self, trace: &Trace) -> Rc<FlameGraph> {
+        self.sync_cache(trace);
+        self.cache
+            .flame_graph
+            .get_or_insert_with(|| Rc::new(trace.flame_graph()))
+            .clone()
+    }
+
+    fn top_self_time(&mut self, trace: &Trace) -> &[usize] {
+        self.sync_cache(trace);
+        self.cache.top_self_time.get_or_insert_with(|| {
+            let mut by_self_time = (0..trace.spans.len()).collect::<Vec<_>>();
+            let key = |i: &usize| std::cmp::Reverse(trace.spans[*i].self_nanos);
+            if by_self_time.len() > TOP_SELF_TIME_ROWS {
+                by_self_time.select_nth_unstable_by_key(TOP_SELF_TIME_ROWS, key);
+                by_self_time.truncate(TOP_SELF_TIME_ROWS);
+            }
+            by_self_time.sort_by_key(key);
+            by_self_time
+        })
+    }
+
+    /// Visible time range, falling back to the root span's duration.
+    fn window(&self, trace: &Trace) -> (i64, i64) {
+        self.window
+            .unwrap_or((0, trace.spans[0].duration_nanos.max(1)))
+    }
+
+    /// Show `start..end`, clamped to the root span and to a minimum
+    /// length of [`MIN_WINDOW_NANOS`].
+    fn set_window(&mut self, trace: &Trace, start: i64, end: i64) {
+        let total = trace.spans[0].duration_nanos.max(1);
+        let len = (end - start).clamp(MIN_WINDOW_NANOS.min(total), total);
+        let start = start.clamp(0, total - len);
+        self.window = if len == total {
+            None
+        } else {
+            Some((start, start + len))
+        };
+    }
+
+    /// Zoom by `factor` (> 1.0 zooms in), keeping `anchor` at the
+    /// same screen position.
+    fn zoom(&mut self, trace: &Trace, factor: f32, anchor: i64) {
+        let (start, end) = self.window(trace);
+        #[allow(clippy::cast_possible_truncation, clippy::cast_precision_loss)]
+        let scale = |nanos: i64| (nanos as f64 / f64::from(factor)) as i64;
+        self.set_window(
+            trace,
+            anchor - scale(anchor - start),
+            anchor + scale(end - anchor),
+        );
+    }
+
+    /// Keep the current zoom level but center the window on `nanos`.
+    fn center_on(&mut self, trace: &Trace, nanos: i64) {
+        let (start, end) = self.window(trace);
+        let half = (end - start) / 2;
+        self.set_window(trace, nanos - half, nanos + half);
+    }
+
+    /// Shift the visible window by `nanos`.
+    fn pan(&mut self, trace: &Trace, nanos: i64) {
+        let (start, end) = self.window(trace);
+        self.set_window(trace, start + nanos, end + nanos);
+    }
+
+    /// Collapse every span with children at or below `depth`.
+    fn collapse_below(&mut self, trace: &Trace, depth: usize) {
+        self.collapsed = trace
+            .spans
+            .iter()
+            .enumerate()
+            .filter(|(i, span)| span.level >= depth && !trace.children(*i).is_empty())
+            .map(|(_, span)| span.id.clone())
+            .collect();
+        self.invalidate_rows();
+    }
+
+    fn expand_all(&mut self) {
+        self.collapsed.clear();
+        self.invalidate_rows();
+    }
+
+    fn toggle_collapsed(&mut self, id: &str) {
+        if !self.collapsed.remove(id) {
+            self.collapsed.insert(id.to_string());
+        }
+        self.invalidate_rows();
+    }
+
+    /// Expand every ancestor of the span at `idx` so it is visible.
+    pub(crate) fn expand_path_to(&mut self, trace: &Trace, idx: usize) {
+        let mut expanded = false;
+        let mut current = trace.parent(idx);
+        while let Some(parent) = current {
+            expanded |= self.collapsed.remove(&trace.spans[parent].id);
+            current = trace.parent(parent);
+        }
+        if expanded {
+            self.invalidate_rows();
+        }
+    }
+}
+
+/// Number of rows listed in the "Top self time" table.
+const TOP_SELF_TIME_ROWS: usize = 10;
+
+/// Height of each waterfall row.
+const ROW_HEIGHT: f32 = 20.0;
+
+/// Widths of the span name and duration columns. Fixed, since only
+/// the visible rows are laid out each frame.
+const NAME_COLUMN_WIDTH: f32 = 320.0;
+const DURATION_COLUMN_WIDTH: f32 = 80.0;
+
+/// Horizontal space between waterfall columns.
+const COLUMN_SPACING: f32 = 10.0;
+
+/// Width and height of the expand/collapse toggle in each row.
+const DISCLOSURE_SIZE: f32 = 14.0;
+
+/// Shortest time range the waterfall can be zoomed to.
+const MIN_WINDOW_NANOS: i64 = 100;
+
+/// Zoom factor applied by the zoom in/out buttons.
+const ZOOM_STEP: f32 = 1.5;
+
+/// Height of the time axis ruler above the waterfall.
+const RULER_HEIGHT: f32 = 32.0;
+
+/// Number of entries shown in the color legend.
+const LEGEND_ENTRIES: usize = 12;
+
+/// Height of the trace overview above the ruler.
+const MINIMAP_HEIGHT: f32 = 60.0;
+
+/// Minimum horizontal space, in points, between ruler ticks.
+const TICK_SPACING: f32 = 140.0;
+
+/// Mapping between trace time (nanosecond offsets from the root
+/// span) and horizontal screen position within the bar column.
+#[derive(Debug, Clone, Copy)]
+struct Timeline {
+    start: i64,
+    end: i64,
+    left: f32,
+    width: f32,
 }
 
-impl crate::Panel for Waterfall {
+impl Timeline {
+    fn new((start, end): (i64, i64), rect: Rect) -> Self {
+        Self {
+            start,
+            end,
+            left: rect.left(),
+            width: rect.width().max(1.0),
+        }
+    }
+
+    fn len(&self) -> i64 {
+        (self.end - self.start).max(1)
+    }
+
+    #[allow(clippy::cast_possible_truncation, clippy::cast_precision_loss)]
+    fn nanos_at(&self, x: f32) -> i64 {
+        self.start + (f64::from((x - self.left) / self.width) * self.len() as f64) as i64
+    }
+
+    #[allow(clippy::cast_possible_truncation, clippy::cast_precision_loss)]
+    fn nanos_per_point(&self) -> f64 {
+        self.len() as f64 / f64::from(self.width)
+    }
+
+    #[allow(clippy::cast_possible_truncation, clippy::cast_precision_loss)]
+    fn x_at(&self, nanos: i64) -> f32 {
+        self.left + ((nanos - self.start) as f64 / self.nanos_per_point()) as f32
+    }
+
+    /// Distance between ticks: the smallest 1, 2 or 5 × 10ⁿ
+    /// nanoseconds that keeps ticks at least [`TICK_SPACING`] apart.
+    #[allow(clippy::cast_possible_truncation)]
+    fn tick_step(&self) -> i64 {
+        let min_step = (self.nanos_per_point() * f64::from(TICK_SPACING)).max(1.0);
+        let magnitude = 10f64.powf(min_step.log10().floor());
+        let step = [1.0, 2.0, 5.0, 10.0]
+            .into_iter()
+            .map(|m| m * magnitude)
+            .find(|step| *step >= min_step)
+            .unwrap_or(10.0 * magnitude);
+        (step as i64).max(1)
+    }
+
+    /// Tick positions within the visible window, in nanoseconds.
+    fn ticks(&self) -> impl Iterator<Item = i64> {
+        let step = self.tick_step();
+        let (start, end) = (self.start, self.end);
+        let first = start.div_euclid(step) * step;
+        (0..)
+            .map(move |i| first + i * step)
+            .skip_while(move |tick| *tick < start)
+            .take_while(move |tick| *tick <= end)
+    }
+}
+
+/// Portion of `start..end` inside `window` as (offset, width)
+/// fractions of the window, or `None` if it lies entirely outside.
+#[allow(clippy::cast_possible_truncation, clippy::cast_precision_loss)]
+fn clip_to_window(
+    start: i64,
+    end: i64,
+    (window_start, window_end): (i64, i64),
+) -> Option<(f32, f32)> {
+    if end < window_start || start > window_end {
+        return None;
+    }
+    let len = (window_end - window_start).max(1) as f64;
+    let clipped_start = start.max(window_start) - window_start;
+    let clipped_end = end.min(window_end) - window_start;
+    Some((
+        (clipped_start as f64 / len) as f32,
+        ((clipped_end - clipped_start) as f64 / len) as f32,
+    ))
+}
+
+pub(crate) struct Waterfall<'a> {
+    trace: &'a Trace,
+    state: &'a mut State,
+}
+
+impl<'a> Waterfall<'a> {
+    pub(crate) fn new(trace: &'a Trace, state: &'a mut State) -> Self {
+        Self { trace, state }
+    }
+}
+
+impl<'a> crate::Panel for Waterfall<'a> {
     fn draw(&mut self, ui: &mut eframe::egui::Ui) -> Option<crate::Action> {
-        // TODO: pre-calculate colors
-        let colors: Vec<Color32> = vec![
-            // Color32::from_rgb(0x07, 0x10, 0x13), // Rich black; ideally the BG color
-            Color32::from_rgb(0x0B, 0x6E, 0x4F), // Dartmouth Green
-            Color32::from_rgb(0xF2, 0x54, 0x5B), // Indian Red
-            Color32::from_rgb(0x64, 0x5E, 0x9D), // Ultra Violet
-            Color32::from_rgb(0x2D, 0xC2, 0xBD), // Robin Egg Blue
-        ];
-
-        // TODO: expand/collapse
-        ui.heading(format!("Trace: {}", self.trace.id.clone()));
+        self.state.sync_cache(self.trace);
+
+        ui.heading(format!("Trace: {}", self.trace.id));
+        ui.horizontal(|ui| {
+            ui.selectable_value(&mut self.state.view, View::Waterfall, "Waterfall");
+            ui.selectable_value(&mut self.state.view, View::FlameGraph, "Flame graph");
+        });
+        if self.state.view == View::FlameGraph {
+            let graph = self.state.flame_graph(self.trace);
+            return crate::flame_graph::FlameGraph::new(&graph, &mut self.state.flame_graph)
+                .draw(ui);
+        }
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.state.show_critical_path, "Critical path");
+            ui.checkbox(&mut self.state.shade_self_time, "Shade self time");
+            ui.separator();
+            if ui.button("Collapse below depth").clicked() {
+                self.state
+                    .collapse_below(self.trace, self.state.collapse_depth);
+            }
+            ui.add(DragValue::new(&mut self.state.collapse_depth).clamp_range(0..=64));
+            if ui.button("Expand all").clicked() {
+                self.state.expand_all();
+            }
+            ui.separator();
+            let (start, end) = self.state.window(self.trace);
+            let center = start + (end - start) / 2;
+            if ui
+                .button("＋")
+                .on_hover_text("Zoom in (ctrl + scroll)")
+                .clicked()
+            {
+                self.state.zoom(self.trace, ZOOM_STEP, center);
+            }
+            if ui
+                .button("－")
+                .on_hover_text("Zoom out (ctrl + scroll)")
+                .clicked()
+            {
+                self.state.zoom(self.trace, 1.0 / ZOOM_STEP, center);
+            }
+            if ui
+                .add_enabled(self.state.window.is_some(), Button::new("Reset zoom"))
+                .on_hover_text("Double-click the timeline to reset")
+                .clicked()
+            {
+                self.state.window = None;
+            }
+        });
+        self.draw_color_options(ui);
+        self.draw_search(ui);
+        let colors = self.state.colors(self.trace);
 
         let mut action = None;
-        #[allow(clippy::cast_precision_loss)]
-        ScrollArea::vertical().show(ui, |ui| {
-            Grid::new("trace_waterfall")
-                .num_columns(3)
-                .spacing((10.0, -7.0))
-                .striped(true)
-                .show(ui, |ui| {
-                    let root = &self.trace.spans[0];
-                    self.trace
-                        .spans
-                        .iter()
-                        .map(|span| {
-                            let width = span.duration_micros as f32 / root.duration_micros as f32;
-                            let offset = span.offset_micros as f32 / root.duration_micros as f32;
-                            let duration_ms = span.duration_micros as f32 / 1000.0;
-                            (span, width, offset, duration_ms)
-                        })
-                        .enumerate()
-                        .zip(colors.iter().cycle())
-                        .for_each(|((i, (span, width, offset, duration_ms)), color)| {
-                            Frame::group(&Style::default()) // with group, bar preview destroys alignment
-                                .stroke(Stroke::NONE)
-                                .show(ui, |ui| {
-                                    ui.add(
-                                        Bar::new(
-                                            BarMode::Fixed,
-                                            5.0,
-                                            15.0 * span.level as f32,
-                                            20.0,
-                                            *color,
-                                        )
-                                        .round_radius(2.0),
-                                    );
-                                    if ui.link(&span.name).clicked() {
-                                        action = Some(crate::Action::OpenSpanAttributes(i));
-                                    }
-                                });
+
+        let critical_path = self
+            .state
+            .show_critical_path
+            .then(|| self.state.critical_path(self.trace));
+        if let Some(critical_path) = &critical_path {
+            ui.collapsing("Critical path breakdown", |ui| {
+                Grid::new("critical_path_totals")
+                    .num_columns(3)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for total in critical_path.totals(self.trace) {
+                            ui.label(if total.service.is_empty() {
+                                "-"
+                            } else {
+                                &total.service
+                            });
+                            ui.label(&total.name);
                             ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
-                                ui.label(format!("{duration_ms} ms"));
+                                ui.label(format_duration(total.nanos));
                             });
-                            ui.add(
-                                Bar::new(BarMode::Relative, width, offset, 20.0, *color)
-                                    .min_width(2.0)
-                                    .round_radius(2.0),
-                            );
                             ui.end_row();
+                        }
+                    });
+            });
+        }
+        ui.collapsing("Top self time", |ui| {
+            let top = self.state.top_self_time(self.trace).to_vec();
+            Grid::new("top_self_time")
+                .num_columns(3)
+                .striped(true)
+                .show(ui, |ui| {
+                    for i in top {
+                        let span = &self.trace.spans[i];
+                        if ui.link(&span.name).clicked() {
+                            self.state.expand_path_to(self.trace, i);
+                            action = Some(crate::Action::OpenSpanAttributes(span.id.clone()));
+                        }
+                        ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+                            ui.label(format_duration(span.self_nanos));
+                        });
+                        ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+                            ui.label(format_duration(span.duration_nanos));
                         });
+                        ui.end_row();
+                    }
                 });
         });
+        ui.add_space(5.0);
+
+        self.draw_minimap(ui, &colors);
+        ui.add_space(2.0);
+        let (ruler_rect, _) = ui.allocate_exact_size(
+            Vec2::new(ui.available_width(), RULER_HEIGHT),
+            Sense::hover(),
+        );
+        if let Some(idx) = self.state.reveal.take() {
+            self.reveal(ui, idx);
+        }
+        let window = self.state.window(self.trace);
+        let mut timeline_rect: Option<Rect> = None;
+        let mut scroll_area = ScrollArea::vertical();
+        if let Some(offset) = self.state.scroll_to.take() {
+            scroll_area = scroll_area.vertical_scroll_offset(offset.max(0.0));
+        }
+        let rows = self.state.rows(self.trace);
+        #[allow(clippy::cast_precision_loss)]
+        let scroll = scroll_area.show_rows(ui, ROW_HEIGHT, rows.len(), |ui, visible| {
+            // stripes first, then gridlines, then the rows themselves
+            let pitch = ROW_HEIGHT + ui.spacing().item_spacing.y;
+            let top = ui.cursor().top();
+            for (n, row) in visible.clone().enumerate() {
+                if row % 2 == 1 {
+                    let y = top + n as f32 * pitch;
+                    ui.painter().rect_filled(
+                        Rect::from_x_y_ranges(ui.max_rect().x_range(), y..=y + ROW_HEIGHT),
+                        Rounding::none(),
+                        ui.visuals().faint_bg_color,
+                    );
+                }
+            }
+            let gridlines = ui.painter().add(Shape::Noop);
+            for row in visible {
+                let rect = self.draw_row(
+                    ui,
+                    rows[row],
+                    critical_path.as_deref(),
+                    &colors,
+                    window,
+                    &mut action,
+                );
+                timeline_rect = Some(timeline_rect.map_or(rect, |r| r.union(rect)));
+            }
+            if let Some(rect) = timeline_rect {
+                let timeline = Timeline::new(window, rect);
+                let stroke = Stroke::new(1.0, ui.visuals().widgets.noninteractive.bg_stroke.color);
+                ui.painter().set(
+                    gridlines,
+                    Shape::Vec(
+                        timeline
+                            .ticks()
+                            .map(|tick| {
+                                let x = timeline.x_at(tick);
+                                Shape::line_segment(
+                                    [Pos2::new(x, rect.top()), Pos2::new(x, rect.bottom())],
+                                    stroke,
+                                )
+                            })
+                            .collect(),
+                    ),
+                );
+                self.interact_timeline(ui, rect);
+            }
+        });
+
+        self.state.scroll_offset = scroll.state.offset.y;
+        self.state.content_height = scroll.content_size.y;
+        self.state.viewport_height = scroll.inner_rect.height();
+
+        if let Some(rect) = timeline_rect {
+            let timeline = Timeline::new(window, rect);
+            self.draw_ruler(ui, ruler_rect, &timeline);
+            Self::draw_crosshair(
+                ui,
+                ruler_rect.union(scroll.inner_rect),
+                ruler_rect,
+                &timeline,
+            );
+        }
         action
     }
 }
 
+impl<'a> Waterfall<'a> {
+    /// Search box with next/previous buttons and the position of the
+    /// current match. Enter moves to the next match, shift + enter to
+    /// the previous.
+    fn draw_search(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Search");
+            let response = ui.add(
+                TextEdit::singleline(&mut self.state.search)
+                    .hint_text("name, ID, attribute or key=value"),
+            );
+            if response.changed() {
+                self.state.current_match = None;
+            }
+            let matches = self.state.matches(self.trace);
+            let mut step = None;
+            if response.lost_focus() && ui.input(|i| i.key_pressed(Key::Enter)) {
+                step = Some(!ui.input(|i| i.modifiers.shift));
+                response.request_focus();
+            }
+            let enabled = !matches.is_empty();
+            if ui
+                .add_enabled(enabled, Button::new("⏶"))
+                .on_hover_text("Previous match (shift + enter)")
+                .clicked()
+            {
+                step = Some(false);
+            }
+            if ui
+                .add_enabled(enabled, Button::new("⏷"))
+                .on_hover_text("Next match (enter)")
+                .clicked()
+            {
+                step = Some(true);
+            }
+            if let Some(forward) = step {
+                self.state.step_match(self.trace, forward);
+            }
+            if !self.state.search.trim().is_empty() {
+                let position = self
+                    .state
+                    .current_match
+                    .as_deref()
+                    .and_then(|id| self.trace.span_index(id))
+                    .and_then(|current| matches.binary_search(&current).ok());
+                match position {
+                    _ if matches.is_empty() => ui.weak("no matches"),
+                    Some(position) => ui.label(format!("{} / {}", position + 1, matches.len())),
+                    None => ui.label(format!("{} matches", matches.len())),
+                };
+            }
+        });
+    }
+
+    /// Scroll the waterfall so the span at `idx` is vertically
+    /// centered, and pan to it if it lies outside the visible window.
+    #[allow(clippy::cast_precision_loss)]
+    fn reveal(&mut self, ui: &Ui, idx: usize) {
+        let rows = self.state.rows(self.trace);
+        let row = rows
+            .binary_search_by_key(&idx, |row| row.span)
+            .unwrap_or_else(|row| row.saturating_sub(1));
+        let pitch = ROW_HEIGHT + ui.spacing().item_spacing.y;
+        self.state.scroll_to =
+            Some(row as f32 * pitch - (self.state.viewport_height - ROW_HEIGHT).max(0.0) / 2.0);
+
+        let span = &self.trace.spans[idx];
+        let (start, end) = self.state.window(self.trace);
+        if span.end_offset_nanos() < start || span.offset_nanos > end {
+            self.state.center_on(self.trace, span.offset_nanos);
+        }
+        ui.ctx().request_repaint();
+    }
+
+    /// "Color by" selector and a legend of the most common colors.
+    fn draw_color_options(&mut self, ui: &mut Ui) {
+        ui.horizontal_wrapped(|ui| {
+            ui.label("Color by");
+            let mut color_by = self.state.color_by.clone();
+            ComboBox::from_id_source("waterfall_color_by")
+                .selected_text(color_by.label())
+                .show_ui(ui, |ui| {
+                    for option in [
+                        ColorBy::Service,
+                        ColorBy::Name,
+                        ColorBy::Status,
+                        ColorBy::Kind,
+                    ] {
+                        let label = option.label();
+                        ui.selectable_value(&mut color_by, option, label);
+                    }
+                    for key in self.state.attribute_keys(self.trace).iter() {
+                        let option = ColorBy::Attribute(key.clone());
+                        let label = option.label();
+                        ui.selectable_value(&mut color_by, option, label);
+                    }
+                });
+            self.state.color_by = color_by;
+
+            ui.separator();
+            let legend = self.state.legend(self.trace);
+            for (key, color, count) in legend.iter().take(LEGEND_ENTRIES) {
+                let (swatch, _) = ui.allocate_exact_size(Vec2::splat(10.0), Sense::hover());
+                ui.painter()
+                    .rect_filled(swatch, Rounding::same(2.0), *color);
+                ui.label(key).on_hover_text(format!("{count} spans"));
+            }
+            if legend.len() > LEGEND_ENTRIES {
+                ui.weak(format!("+{} more", legend.len() - LEGEND_ENTRIES));
+            }
+        });
+    }
+
+    /// Lay out a single waterfall row, returning the rect of its bar
+    /// column.
+    #[allow(clippy::cast_precision_loss)]
+    fn draw_row(
+        &mut self,
+        ui: &mut Ui,
+        row: Row,
+        critical_path: Option<&CriticalPath>,
+        colors: &[Color32],
+        window: (i64, i64),
+        action: &mut Option<crate::Action>,
+    ) -> Rect {
+        let i = row.span;
+        let span = &self.trace.spans[i];
+        let (rect, _) =
+            ui.allocate_exact_size(Vec2::new(ui.available_width(), ROW_HEIGHT), Sense::hover());
+
+        let is_match = self.state.is_match(i);
+        let color = colors[i];
+        let color = match (critical_path, is_match) {
+            (_, Some(false)) => color.gamma_multiply(0.25),
+            (Some(path), _) if !path.contains(i) => color.gamma_multiply(0.25),
+            _ => color,
+        };
+        if is_match == Some(true) {
+            let fill = ui.visuals().selection.bg_fill;
+            let fill = if self.state.current_match.as_ref() == Some(&span.id) {
+                fill.gamma_multiply(0.6)
+            } else {
+                fill.gamma_multiply(0.25)
+            };
+            ui.painter().rect_filled(rect, Rounding::none(), fill);
+        }
+
+        let mut left = rect.left();
+        let mut column = |width: f32| {
+            let column = Rect::from_x_y_ranges(left..=left + width, rect.y_range());
+            left += width + COLUMN_SPACING;
+            column
+        };
+        let name_rect = column(NAME_COLUMN_WIDTH);
+        let duration_rect = column(DURATION_COLUMN_WIDTH);
+        let self_rect = column(DURATION_COLUMN_WIDTH);
+        let bar_rect = Rect::from_x_y_ranges(left..=rect.right().max(left + 1.0), rect.y_range());
+        let mut cell = |rect: Rect, column: &str, layout: Layout| {
+            let mut cell = ui.child_ui_with_id_source(rect, layout, (i, column));
+            cell.set_clip_rect(rect.intersect(ui.clip_rect()));
+            cell
+        };
+
+        let mut name = cell(name_rect, "name", Layout::left_to_right(Align::Center));
+        if is_match == Some(false) {
+            let weak = name.visuals().weak_text_color();
+            name.visuals_mut().hyperlink_color = weak;
+        }
+        name.add(
+            Bar::new(
+                BarMode::Fixed,
+                5.0,
+                15.0 * span.level as f32,
+                ROW_HEIGHT,
+                color,
+            )
+            .round_radius(2.0),
+        );
+        if self.trace.children(i).is_empty() {
+            name.allocate_space(Vec2::splat(DISCLOSURE_SIZE));
+        } else {
+            let icon = if row.hidden > 0 { "⏵" } else { "⏷" };
+            if name
+                .add_sized(Vec2::splat(DISCLOSURE_SIZE), Button::new(icon).frame(false))
+                .clicked()
+            {
+                self.state.toggle_collapsed(&span.id);
+            }
+        }
+        if name.link(&span.name).clicked() {
+            *action = Some(crate::Action::OpenSpanAttributes(span.id.clone()));
+        }
+        if row.hidden > 0 {
+            name.weak(format!("(+{})", row.hidden));
+        }
+
+        cell(
+            duration_rect,
+            "duration",
+            Layout::right_to_left(Align::Center),
+        )
+        .label(format_duration(span.duration_nanos));
+        cell(self_rect, "self", Layout::right_to_left(Align::Center))
+            .label(format_duration(span.self_nanos));
+
+        let self_ratio = if self.state.shade_self_time {
+            span.self_nanos as f32 / span.duration_nanos.max(1) as f32
+        } else {
+            1.0
+        };
+        let clipped = clip_to_window(span.offset_nanos, span.end_offset_nanos(), window);
+        let bar = match clipped {
+            Some((offset, width)) => Bar::new(BarMode::Relative, width, offset, ROW_HEIGHT, color)
+                .min_width(2.0)
+                .round_radius(2.0)
+                .solid_fraction(self_ratio),
+            None => Bar::new(
+                BarMode::Relative,
+                0.0,
+                0.0,
+                ROW_HEIGHT,
+                Color32::TRANSPARENT,
+            ),
+        };
+        let response = cell(bar_rect, "bar", Layout::left_to_right(Align::Center)).add(bar);
+        if clipped.is_some() {
+            response.on_hover_ui_at_pointer(|ui| crate::attributes::tooltip(ui, span));
+        }
+        bar_rect
+    }
+
+    /// Overview of every span across the root span's full duration,
+    /// with the visible part of the waterfall outlined. Dragging the
+    /// outline pans and scrolls, dragging elsewhere zooms to the
+    /// selected range, and clicking centers the view on that point.
+    #[allow(clippy::cast_precision_loss)]
+    fn draw_minimap(&mut self, ui: &mut Ui, colors: &[Color32]) {
+        let (rect, response) = ui.allocate_exact_size(
+            Vec2::new(ui.available_width(), MINIMAP_HEIGHT),
+            Sense::click_and_drag(),
+        );
+        let full = Timeline::new((0, self.trace.spans[0].duration_nanos.max(1)), rect);
+
+        let painter = ui.painter().with_clip_rect(rect);
+        let visuals = ui.visuals();
+        painter.rect_filled(rect, Rounding::none(), visuals.extreme_bg_color);
+        let lines = match &self.state.cache.minimap {
+            Some((cached_rect, lines)) if *cached_rect == rect => lines.clone(),
+            _ => {
+                let lines = self.minimap_lines(rect, full, colors);
+                self.state.cache.minimap = Some((rect, lines.clone()));
+                lines
+            }
+        };
+        painter.extend(lines);
+
+        let (start, end) = self.state.window(self.trace);
+        let (top, height) = if self.state.content_height > 0.0 {
+            (
+                self.state.scroll_offset / self.state.content_height,
+                (self.state.viewport_height / self.state.content_height).min(1.0),
+            )
+        } else {
+            (0.0, 1.0)
+        };
+        let viewport = Rect::from_min_max(
+            Pos2::new(full.x_at(start), rect.top() + top * rect.height()),
+            Pos2::new(full.x_at(end), rect.top() + (top + height) * rect.height()),
+        );
+        painter.rect_filled(
+            viewport,
+            Rounding::same(1.0),
+            visuals.selection.bg_fill.gamma_multiply(0.2),
+        );
+        painter.rect_stroke(
+            viewport,
+            Rounding::same(1.0),
+            Stroke::new(1.5, visuals.strong_text_color()),
+        );
+
+        let previous = (self.state.window, self.state.scroll_to);
+        if response.drag_started() {
+            self.state.minimap_drag = response.interact_pointer_pos().map(|pos| {
+                if viewport.contains(pos) {
+                    MinimapDrag::Viewport
+                } else {
+                    MinimapDrag::Select(pos.x)
+                }
+            });
+        }
+        match self.state.minimap_drag {
+            Some(MinimapDrag::Viewport) if response.dragged() => {
+                let delta = response.drag_delta();
+                #[allow(clippy::cast_possible_truncation)]
+                let nanos = (f64::from(delta.x) * full.nanos_per_point()) as i64;
+                self.state.pan(self.trace, nanos);
+                self.state.scroll_to = Some(
+                    self.state.scroll_offset + delta.y / rect.height() * self.state.content_height,
+                );
+            }
+            Some(MinimapDrag::Select(start_x)) => {
+                if let Some(pointer) = response.interact_pointer_pos() {
+                    let selection = Rect::from_x_y_ranges(
+                        start_x.min(pointer.x)..=start_x.max(pointer.x),
+                        rect.y_range(),
+                    );
+                    painter.rect_filled(
+                        selection,
+                        Rounding::none(),
+                        visuals.selection.bg_fill.gamma_multiply(0.3),
+                    );
+                    if response.drag_released() && selection.width() > 3.0 {
+                        self.state.set_window(
+                            self.trace,
+                            full.nanos_at(selection.left()),
+                            full.nanos_at(selection.right()),
+                        );
+                    }
+                }
+            }
+            _ => {}
+        }
+        if response.drag_released() {
+            self.state.minimap_drag = None;
+        }
+
+        if response.clicked() {
+            if let Some(pointer) = response.interact_pointer_pos() {
+                self.state.center_on(self.trace, full.nanos_at(pointer.x));
+                self.state.scroll_to = Some(
+                    (pointer.y - rect.top()) / rect.height() * self.state.content_height
+                        - self.state.viewport_height / 2.0,
+                );
+            }
+        }
+
+        if (self.state.window, self.state.scroll_to) != previous {
+            ui.ctx().request_repaint();
+        }
+    }
+
+    /// One line per span, placed on the span's waterfall row (hidden
+    /// spans share the row of their collapsed ancestor). Lines landing
+    /// on the same pixel row are merged where they overlap, so the
+    /// shape count is bounded by the minimap's size rather than the
+    /// span count.
+    #[allow(clippy::cast_precision_loss)]
+    fn minimap_lines(&mut self, rect: Rect, full: Timeline, colors: &[Color32]) -> Vec<Shape> {
+        let rows = self.state.rows(self.trace);
+        let row_height = rect.height() / rows.len().max(1) as f32;
+        let stroke_width = row_height.clamp(1.0, 3.0);
+
+        let mut lines = Vec::new();
+        let mut pending: Vec<(f32, f32, Color32)> = Vec::new();
+        let mut pending_y = f32::NAN;
+        let mut flush = |y: f32, pending: &mut Vec<(f32, f32, Color32)>| {
+            pending.sort_by(|l, r| l.0.total_cmp(&r.0));
+            let mut merged: Option<(f32, f32, Color32)> = None;
+            for &(start, end, color) in pending.iter() {
+                match &mut merged {
+                    Some((_, merged_end, _)) if start <= *merged_end + 1.0 => {
+                        *merged_end = merged_end.max(end);
+                    }
+                    _ => {
+                        if let Some((start, end, color)) = merged.replace((start, end, color)) {
+                            lines.push(Shape::line_segment(
+                                [Pos2::new(start, y), Pos2::new(end, y)],
+                                Stroke::new(stroke_width, color),
+                            ));
+                        }
+                    }
+                }
+            }
+            if let Some((start, end, color)) = merged {
+                lines.push(Shape::line_segment(
+                    [Pos2::new(start, y), Pos2::new(end, y)],
+                    Stroke::new(stroke_width, color),
+                ));
+            }
+            pending.clear();
+        };
+
+        // rows are in span order, so pixel rows only ever increase
+        for (row_idx, row) in rows.iter().enumerate() {
+            let y = (rect.top() + (row_idx as f32 + 0.5) * row_height).round();
+            if y != pending_y {
+                flush(pending_y, &mut pending);
+                pending_y = y;
+            }
+            let spans = row.span..=row.span + row.hidden;
+            for (span, color) in self.trace.spans[spans.clone()].iter().zip(&colors[spans]) {
+                let start = full.x_at(span.offset_nanos);
+                let end = full.x_at(span.end_offset_nanos()).max(start + 1.0);
+                pending.push((start, end, *color));
+            }
+        }
+        flush(pending_y, &mut pending);
+        lines
+    }
+
+    /// Draw tick marks labelled with the offset from the start of the
+    /// trace and the corresponding wall-clock time.
+    fn draw_ruler(&self, ui: &Ui, rect: Rect, timeline: &Timeline) {
+        let painter = ui.painter().with_clip_rect(rect);
+        let visuals = ui.visuals();
+        painter.rect_filled(rect, Rounding::none(), visuals.extreme_bg_color);
+
+        let step = timeline.tick_step();
+        let wall_clock_format = match step {
+            0..=999 => "%H:%M:%S%.9f",
+            1_000..=999_999 => "%H:%M:%S%.6f",
+            _ => "%H:%M:%S%.3f",
+        };
+        let font = FontId::monospace(10.0);
+        let stroke = Stroke::new(1.0, visuals.text_color());
+        for tick in timeline.ticks() {
+            let x = timeline.x_at(tick);
+            painter.line_segment(
+                [
+                    Pos2::new(x, rect.bottom() - 6.0),
+                    Pos2::new(x, rect.bottom()),
+                ],
+                stroke,
+            );
+            painter.text(
+                Pos2::new(x + 2.0, rect.top() + 2.0),
+                Align2::LEFT_TOP,
+                format!("+{}", format_duration(tick)),
+                font.clone(),
+                visuals.text_color(),
+            );
+            painter.text(
+                Pos2::new(x + 2.0, rect.top() + 14.0),
+                Align2::LEFT_TOP,
+                self.trace.timestamp_at(tick).format(wall_clock_format),
+                font.clone(),
+                visuals.weak_text_color(),
+            );
+        }
+    }
+
+    /// Vertical line under the pointer through the ruler and bars,
+    /// labelled in the ruler with the time at that position.
+    fn draw_crosshair(ui: &Ui, area: Rect, ruler_rect: Rect, timeline: &Timeline) {
+        let Some(pointer) = ui.ctx().pointer_hover_pos() else {
+            return;
+        };
+        let column = timeline.left..=timeline.left + timeline.width;
+        if !area.contains(pointer) || !column.contains(&pointer.x) {
+            return;
+        }
+
+        let painter = ui.painter().with_clip_rect(area);
+        let visuals = ui.visuals();
+        painter.vline(
+            pointer.x,
+            area.y_range(),
+            Stroke::new(1.0, visuals.strong_text_color()),
+        );
+        let label = painter.layout_no_wrap(
+            format!("+{}", format_duration(timeline.nanos_at(pointer.x))),
+            FontId::monospace(10.0),
+            visuals.strong_text_color(),
+        );
+        let label_rect = Align2::LEFT_BOTTOM.anchor_rect(Rect::from_min_size(
+            Pos2::new(pointer.x + 4.0, ruler_rect.bottom() - 1.0),
+            label.size(),
+        ));
+        painter.rect_filled(
+            label_rect.expand(2.0),
+            Rounding::same(2.0),
+            visuals.extreme_bg_color,
+        );
+        painter.galley(label_rect.min, label);
+    }
+
+    /// Zoom with ctrl + scroll, pan with horizontal scroll or a
+    /// middle-button drag, zoom to a range by dragging across it, and
+    /// reset zoom by double-clicking.
+    fn interact_timeline(&mut self, ui: &mut Ui, rect: Rect) {
+        let previous_window = self.state.window;
+        let timeline = Timeline::new(self.state.window(self.trace), rect);
+        let response = ui.interact(rect, ui.id().with("timeline"), Sense::click_and_drag());
+
+        if let Some(pointer) = response.hover_pos() {
+            let (zoom, scroll) = ui.input(|i| (i.zoom_delta(), i.scroll_delta.x));
+            if zoom != 1.0 {
+                self.state
+                    .zoom(self.trace, zoom, timeline.nanos_at(pointer.x));
+            }
+            if scroll != 0.0 {
+                #[allow(clippy::cast_possible_truncation)]
+                let nanos = (-f64::from(scroll) * timeline.nanos_per_point()) as i64;
+                self.state.pan(self.trace, nanos);
+            }
+        }
+
+        if response.double_clicked() {
+            self.state.window = None;
+        }
+
+        if response.dragged_by(PointerButton::Middle) {
+            #[allow(clippy::cast_possible_truncation)]
+            let nanos = (-f64::from(response.drag_delta().x) * timeline.nanos_per_point()) as i64;
+            self.state.pan(self.trace, nanos);
+        } else if response.drag_started_by(PointerButton::Primary) {
+            self.state.selection_start = response.interact_pointer_pos().map(|pos| pos.x);
+        }
+
+        if let (Some(start_x), Some(pointer)) =
+            (self.state.selection_start, response.interact_pointer_pos())
+        {
+            let selection = Rect::from_x_y_ranges(
+                start_x.min(pointer.x)..=start_x.max(pointer.x),
+                rect.y_range(),
+            );
+            ui.painter().rect_filled(
+                selection,
+                Rounding::none(),
+                ui.visuals().selection.bg_fill.gamma_multiply(0.3),
+            );
+            if response.drag_released() {
+                self.state.selection_start = None;
+                if selection.width() > 3.0 {
+                    self.state.set_window(
+                        self.trace,
+                        timeline.nanos_at(selection.left()),
+                        timeline.nanos_at(selection.right()),
+                    );
+                }
+            }
+        } else if response.drag_released() {
+            self.state.selection_start = None;
+        }
+
+        // bars, ruler and gridlines were laid out with the old window
+        if self.state.window != previous_window {
+            ui.ctx().request_repaint();
+        }
+    }
+}
+
 /// Render modes for [`Bar`]
 #[derive(Debug, Default, PartialEq)]
 enum BarMode {
@@ -126,6 +1297,10 @@ struct Bar {
 
     /// Radius of corner rounding. Set to zero to disable rounding.
     round_radius: f32,
+
+    /// Portion of the bar, from the left, painted in full color. The
+    /// remainder is painted faded. (Must be in range 0.0..=1.0)
+    solid_fraction: f32,
 }
 
 impl Widget for Bar {
@@ -142,7 +1317,7 @@ impl Widget for Bar {
             BarMode::Fixed => (self.width + self.offset, self.offset, self.width),
         };
 
-        let (mut rect, response) =
+        let (mut rect, mut response) =
             ui.allocate_exact_size(Vec2::new(claimed_width, self.height), Sense::hover());
 
         rect.min.x += offset;
@@ -152,9 +1327,22 @@ impl Widget for Bar {
             rect.max.x = rect.min.x + self.min_width;
         }
 
+        // only the painted bar counts as hovered, not the space
+        // claimed around it
+        response.hovered &= response.hover_pos().is_some_and(|pos| rect.contains(pos));
+        response.rect = rect;
+
         if ui.is_rect_visible(rect) {
-            ui.painter()
-                .rect_filled(rect, Rounding::same(self.round_radius), self.color);
+            let rounding = Rounding::same(self.round_radius);
+            if self.solid_fraction < 1.0 {
+                ui.painter()
+                    .rect_filled(rect, rounding, self.color.gamma_multiply(0.35));
+                let mut solid = rect;
+                solid.max.x = solid.min.x + rect.width() * self.solid_fraction;
+                ui.painter().rect_filled(solid, rounding, self.color);
+            } else {
+                ui.painter().rect_filled(rect, rounding, self.color);
+            }
         }
         response
     }
@@ -180,6 +1368,7 @@ impl Bar {
             offset,
             height,
             color,
+            solid_fraction: 1.0,
             ..Default::default()
         }
     }
@@ -193,4 +1382,9 @@ impl Bar {
         self.round_radius = radius;
         self
     }
+
+    fn solid_fraction(mut self, fraction: f32) -> Self {
+        self.solid_fraction = fraction.clamp(0.0, 1.0);
+        self
+    }
 }
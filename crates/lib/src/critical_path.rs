@@ -0,0 +1,213 @@
+//! Critical path through a [`Trace`]: the chain of spans that
+//! determined its end-to-end duration.
+
+use std::collections::{BTreeMap, HashSet};
+
+use crate::Trace;
+
+/// Contiguous stretch of time during which a single span was on the
+/// critical path. Times are nanosecond offsets from the root span.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Segment {
+    /// Index into [`Trace::spans`].
+    pub span: usize,
+    pub start_nanos: i64,
+    pub end_nanos: i64,
+}
+
+impl Segment {
+    #[must_use]
+    pub fn duration_nanos(&self) -> i64 {
+        self.end_nanos - self.start_nanos
+    }
+}
+
+/// Time spent on the critical path by a single operation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OperationTotal {
+    pub service: String,
+    pub name: String,
+    pub nanos: i64,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CriticalPath {
+    /// Segments in chronological order.
+    pub segments: Vec<Segment>,
+    spans: HashSet<usize>,
+}
+
+impl CriticalPath {
+    /// Does any part of the span at `idx` lie on the critical path.
+    #[must_use]
+    pub fn contains(&self, idx: usize) -> bool {
+        self.spans.contains(&idx)
+    }
+
+    /// Sum of critical path time per (`service.name`, span name),
+    /// longest first.
+    #[must_use]
+    pub fn totals(&self, trace: &Trace) -> Vec<OperationTotal> {
+        let totals = self
+            .segments
+            .iter()
+            .fold(BTreeMap::new(), |mut m, segment| {
+                let span = &trace.spans[segment.span];
+                let key = (
+                    span.service_name().unwrap_or_default().to_string(),
+                    span.name.clone(),
+                );
+                *m.entry(key).or_default() += segment.duration_nanos();
+                m
+            });
+        let mut totals = totals
+            .into_iter()
+            .map(|((service, name), nanos)| OperationTotal {
+                service,
+                name,
+                nanos,
+            })
+            .collect::<Vec<_>>();
+        totals.sort_by_key(|total| std::cmp::Reverse(total.nanos));
+        totals
+    }
+}
+
+impl Trace {
+    /// Compute the critical path, walking backwards from the end of
+    /// the root span. At each step the child that finished last
+    /// (clamped to the current point in time) is followed, so
+    /// overlapping async children only contribute the part of their
+    /// runtime that actually blocked their parent.
+    #[must_use]
+    pub fn critical_path(&self) -> CriticalPath {
+        let mut segments = Vec::new();
+        if let Some(root) = self.spans.first() {
+            self.walk_critical_path(0, root.end_offset_nanos(), &mut segments);
+        }
+        segments.reverse();
+        let spans = segments.iter().map(|segment| segment.span).collect();
+        CriticalPath { segments, spans }
+    }
+
+    /// Push segments for the span at `idx` (and its descendants) in
+    /// reverse chronological order, ignoring anything after `until`.
+    fn walk_critical_path(&self, idx: usize, until: i64, acc: &mut Vec<Segment>) {
+        let span = &self.spans[idx];
+        let start = span.offset_nanos;
+        let mut cursor = span.end_offset_nanos().min(until);
+
+        while cursor > start {
+            let next = self
+                .children(idx)
+                .iter()
+                .filter(|&&child| self.spans[child].offset_nanos < cursor)
+                .map(|&child| (child, self.spans[child].end_offset_nanos().min(cursor)))
+                .max_by_key(|(_, end)| *end);
+            let Some((child, child_end)) = next else {
+                break;
+            };
+
+            if child_end < cursor {
+                acc.push(Segment {
+                    span: idx,
+                    start_nanos: child_end,
+                    end_nanos: cursor,
+                });
+            }
+            self.walk_critical_path(child, child_end, acc);
+            cursor = self.spans[child].offset_nanos.max(start);
+        }
+
+        if cursor > start {
+            acc.push(Segment {
+                span: idx,
+                start_nanos: start,
+                end_nanos: cursor,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{test_util::span, Trace};
+
+    fn path_names(trace: &Trace) -> Vec<(String, i64, i64)> {
+        trace
+            .critical_path()
+            .segments
+            .iter()
+            .map(|s| (trace.spans[s.span].name.clone(), s.start_nanos, s.end_nanos))
+            .collect()
+    }
+
+    #[test]
+    fn sequential_children() {
+        let trace = Trace::new(
+            span("root", "root", None, 0, 100),
+            vec![
+                span("a", "a", Some("root"), 10, 30),
+                span("b", "b", Some("root"), 50, 40),
+            ],
+        );
+        assert_eq!(
+            path_names(&trace),
+            vec![
+                ("root".to_string(), 0, 10),
+                ("a".to_string(), 10, 40),
+                ("root".to_string(), 40, 50),
+                ("b".to_string(), 50, 90),
+                ("root".to_string(), 90, 100),
+            ]
+        );
+    }
+
+    #[test]
+    fn overlapping_children_follow_last_to_finish() {
+        let trace = Trace::new(
+            span("root", "root", None, 0, 100),
+            vec![
+                span("fast", "fast", Some("root"), 0, 40),
+                span("slow", "slow", Some("root"), 10, 80),
+            ],
+        );
+        let path = trace.critical_path();
+        assert_eq!(
+            path_names(&trace),
+            vec![
+                ("fast".to_string(), 0, 10),
+                ("slow".to_string(), 10, 90),
+                ("root".to_string(), 90, 100),
+            ]
+        );
+        assert!(path.contains(0));
+    }
+
+    #[test]
+    fn async_child_outliving_parent_is_clamped() {
+        let trace = Trace::new(
+            span("root", "root", None, 0, 50),
+            vec![span("async", "async", Some("root"), 20, 100)],
+        );
+        assert_eq!(
+            path_names(&trace),
+            vec![("root".to_string(), 0, 20), ("async".to_string(), 20, 50)]
+        );
+    }
+
+    #[test]
+    fn totals_group_by_operation() {
+        let trace = Trace::new(
+            span("root", "root", None, 0, 100),
+            vec![
+                span("a", "a", Some("root"), 0, 30),
+                span("b", "b", Some("root"), 40, 20),
+            ],
+        );
+        let totals = trace.critical_path().totals(&trace);
+        assert_eq!(totals[0].name, "root");
+        assert_eq!(totals[0].nanos, 50);
+        assert_eq!(totals.iter().map(|t| t.nanos).sum::<i64>(), 100);
+    }
+}
@@ -0,0 +1,166 @@
+//! Alignment of two [`Trace`]s by span path, for comparing a slow
+//! request against a fast one.
+
+use std::collections::HashMap;
+
+use crate::Trace;
+
+/// How a span differs between the two traces.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change {
+    /// Only in the right trace.
+    Added,
+    /// Only in the left trace.
+    Removed,
+    /// In both traces. Positive when the right span took longer.
+    Delta(i64),
+}
+
+/// A span path present in either trace.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffRow {
+    pub service: String,
+    pub name: String,
+    pub level: usize,
+    /// Index into the left trace's [`Trace::spans`].
+    pub left: Option<usize>,
+    /// Index into the right trace's [`Trace::spans`].
+    pub right: Option<usize>,
+    pub change: Change,
+}
+
+/// Every span path of both traces, in pre-order. Rows only in the
+/// right trace follow the matched and removed rows sharing their
+/// parent.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TraceDiff {
+    pub rows: Vec<DiffRow>,
+}
+
+/// Key identifying a span among its siblings: `service.name`, span
+/// name, and how many earlier siblings share both.
+type PathKey<'a> = (&'a str, &'a str, usize);
+
+/// `indices` keyed by [`PathKey`], keeping their order.
+fn keyed(trace: &Trace, indices: Vec<usize>) -> Vec<(PathKey<'_>, usize)> {
+    let mut seen = HashMap::<(&str, &str), usize>::new();
+    indices
+        .into_iter()
+        .map(|idx| {
+            let span = &trace.spans[idx];
+            let service = span.service_name().unwrap_or_default();
+            let ordinal = seen.entry((service, &span.name)).or_default();
+            let key = (service, span.name.as_str(), *ordinal);
+            *ordinal += 1;
+            (key, idx)
+        })
+        .collect()
+}
+
+/// Append rows for `left_spans` and `right_spans`, which share a
+/// parent path, each followed by the rows of its descendants.
+fn diff_siblings(
+    left: &Trace,
+    right: &Trace,
+    left_spans: Vec<usize>,
+    right_spans: Vec<usize>,
+    level: usize,
+    rows: &mut Vec<DiffRow>,
+) {
+    let right_spans = keyed(right, right_spans);
+    let mut unmatched = right_spans.iter().copied().collect::<HashMap<_, _>>();
+    let mut pairs = Vec::new();
+    for (key, l) in keyed(left, left_spans) {
+        let r = unmatched.remove(&key);
+        pairs.push((key, Some(l), r));
+    }
+    pairs.extend(
+        right_spans
+            .into_iter()
+            .filter(|(key, _)| unmatched.contains_key(key))
+            .map(|(key, r)| (key, None, Some(r))),
+    );
+
+    for ((service, name, _), l, r) in pairs {
+        let change = match (l, r) {
+            (Some(l), Some(r)) => {
+                Change::Delta(right.spans[r].duration_nanos - left.spans[l].duration_nanos)
+            }
+            (Some(_), None) => Change::Removed,
+            _ => Change::Added,
+        };
+        rows.push(DiffRow {
+            service: service.to_string(),
+            name: name.to_string(),
+            level,
+            left: l,
+            right: r,
+            change,
+        });
+        diff_siblings(
+            left,
+            right,
+            l.map_or_else(Vec::new, |l| left.children(l).to_vec()),
+            r.map_or_else(Vec::new, |r| right.children(r).to_vec()),
+            level + 1,
+            rows,
+        );
+    }
+}
+
+/// Align `left` and `right` by span path: the `service.name`, name and
+/// sibling ordinal of a span and each of its ancestors.
+#[must_use]
+pub fn diff(left: &Trace, right: &Trace) -> TraceDiff {
+    let root = |trace: &Trace| {
+        if trace.spans.is_empty() {
+            Vec::new()
+        } else {
+            vec![0]
+        }
+    };
+    let mut rows = Vec::new();
+    diff_siblings(left, right, root(left), root(right), 0, &mut rows);
+    TraceDiff { rows }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Change;
+    use crate::{test_util::span, Trace};
+
+    #[test]
+    fn aligns_by_path_and_sibling_ordinal() {
+        let fast = Trace::new(
+            span("root", "GET /", None, 0, 50),
+            vec![
+                span("q1", "query", Some("root"), 0, 10),
+                span("cache", "cache", Some("root"), 10, 5),
+            ],
+        );
+        let slow = Trace::new(
+            span("root", "GET /", None, 0, 90),
+            vec![
+                span("q1", "query", Some("root"), 0, 30),
+                span("q2", "query", Some("root"), 40, 20),
+                span("q2_lock", "lock", Some("q2"), 40, 5),
+            ],
+        );
+
+        let rows = super::diff(&fast, &slow)
+            .rows
+            .into_iter()
+            .map(|row| (row.name, row.level, row.change))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            rows,
+            vec![
+                ("GET /".to_string(), 0, Change::Delta(40)),
+                ("query".to_string(), 1, Change::Delta(20)),
+                ("cache".to_string(), 1, Change::Removed),
+                ("query".to_string(), 1, Change::Added),
+                ("lock".to_string(), 2, Change::Added),
+            ]
+        );
+    }
+}
@@ -0,0 +1,232 @@
+//! Flame graphs: spans merged by call path (the names of a span and
+//! its ancestors), sized by the time spent beneath each path.
+
+use std::collections::HashMap;
+
+use crate::Trace;
+
+/// Spans sharing a single call path.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Frame {
+    pub name: String,
+    /// Number of ancestors. Roots are at depth zero.
+    pub depth: usize,
+    /// Index into [`FlameGraph::frames`].
+    pub parent: Option<usize>,
+    /// Indices into [`FlameGraph::frames`], sorted by name.
+    pub children: Vec<usize>,
+    /// Number of spans merged into this frame.
+    pub count: usize,
+    /// Sum of the merged spans' durations.
+    pub duration_nanos: i64,
+    /// Sum of the merged spans' self time.
+    pub self_nanos: i64,
+    /// Self time of this frame and every frame beneath it. Frames are
+    /// sized by this, so children always fit within their parent.
+    pub total_nanos: i64,
+    /// Start of this frame, laid out with siblings side by side from
+    /// the start of the parent.
+    pub offset_nanos: i64,
+}
+
+impl Frame {
+    #[must_use]
+    pub fn end_offset_nanos(&self) -> i64 {
+        self.offset_nanos + self.total_nanos
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FlameGraph {
+    /// Every frame. Parents always come before their children.
+    pub frames: Vec<Frame>,
+    /// Indices into [`Self::frames`] of frames without a parent,
+    /// sorted by name.
+    pub roots: Vec<usize>,
+    /// Number of traces merged into the graph.
+    pub traces: usize,
+}
+
+impl FlameGraph {
+    /// Combined [`Frame::total_nanos`] of every root.
+    #[must_use]
+    pub fn total_nanos(&self) -> i64 {
+        self.roots
+            .iter()
+            .map(|&root| self.frames[root].total_nanos)
+            .sum()
+    }
+
+    /// Deepest [`Frame::depth`], or zero when empty.
+    #[must_use]
+    pub fn max_depth(&self) -> usize {
+        self.frames
+            .iter()
+            .map(|frame| frame.depth)
+            .max()
+            .unwrap_or_default()
+    }
+
+    /// Indices of the frames from a root down to `idx`, inclusive.
+    #[must_use]
+    pub fn path_to(&self, idx: usize) -> Vec<usize> {
+        let mut path = vec![idx];
+        while let Some(parent) = self.frames[path[path.len() - 1]].parent {
+            path.push(parent);
+        }
+        path.reverse();
+        path
+    }
+
+    /// Merge every span of `traces` into frames, then lay them out.
+    fn build<'a>(traces: impl IntoIterator<Item = &'a Trace>) -> Self {
+        let mut graph = Self::default();
+        let mut lookup = HashMap::<(Option<usize>, String), usize>::new();
+
+        for trace in traces {
+            graph.traces += 1;
+            // frame for each level of the current span's ancestry
+            let mut stack: Vec<usize> = Vec::new();
+            for span in &trace.spans {
+                stack.truncate(span.level);
+                let parent = stack.last().copied();
+                let idx = *lookup
+                    .entry((parent, span.name.clone()))
+                    .or_insert_with(|| {
+                        graph.frames.push(Frame {
+                            name: span.name.clone(),
+                            depth: stack.len(),
+                            parent,
+                            ..Frame::default()
+                        });
+                        let idx = graph.frames.len() - 1;
+                        match parent {
+                            Some(parent) => graph.frames[parent].children.push(idx),
+                            None => graph.roots.push(idx),
+                        }
+                        idx
+                    });
+                let frame = &mut graph.frames[idx];
+                frame.count += 1;
+                frame.duration_nanos += span.duration_nanos;
+                frame.self_nanos += span.self_nanos;
+                stack.push(idx);
+            }
+        }
+
+        // children always follow their parent, so walking backwards
+        // sees every child's total before its parent needs it
+        for idx in (0..graph.frames.len()).rev() {
+            let children = graph.frames[idx]
+                .children
+                .iter()
+                .map(|&child| graph.frames[child].total_nanos)
+                .sum::<i64>();
+            graph.frames[idx].total_nanos += graph.frames[idx].self_nanos + children;
+        }
+
+        let by_name =
+            |frames: &[Frame], l: &usize, r: &usize| frames[*l].name.cmp(&frames[*r].name);
+        graph.roots.sort_by(|l, r| by_name(&graph.frames, l, r));
+        let mut offset = 0;
+        for &root in &graph.roots {
+            graph.frames[root].offset_nanos = offset;
+            offset += graph.frames[root].total_nanos;
+        }
+        for idx in 0..graph.frames.len() {
+            let mut children = std::mem::take(&mut graph.frames[idx].children);
+            children.sort_by(|l, r| by_name(&graph.frames, l, r));
+            let mut offset = graph.frames[idx].offset_nanos;
+            for &child in &children {
+                graph.frames[child].offset_nanos = offset;
+                offset += graph.frames[child].total_nanos;
+            }
+            graph.frames[idx].children = children;
+        }
+
+        graph
+    }
+}
+
+/// Flame graph of every span in `traces`, merging spans with the same
+/// name under the same path across traces. Divide by
+/// [`FlameGraph::traces`] for the time spent per trace on average.
+#[must_use]
+pub fn flame_graph<'a>(traces: impl IntoIterator<Item = &'a Trace>) -> FlameGraph {
+    FlameGraph::build(traces)
+}
+
+impl Trace {
+    /// Flame graph of this trace, merging spans with the same name
+    /// under the same path.
+    #[must_use]
+    pub fn flame_graph(&self) -> FlameGraph {
+        FlameGraph::build([self])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{test_util::span, Trace};
+
+    #[test]
+    fn merges_identical_paths() -> Result<(), String> {
+        let trace = Trace::new(
+            span("root", "GET /", None, 0, 100),
+            vec![
+                span("q1", "query", Some("root"), 0, 20),
+                span("q2", "query", Some("root"), 30, 20),
+                span("fetch", "fetch", Some("root"), 60, 30),
+                span("q3", "query", Some("fetch"), 60, 10),
+            ],
+        );
+        let graph = trace.flame_graph();
+        assert_eq!(graph.roots.len(), 1);
+        assert_eq!(graph.total_nanos(), 100);
+        assert_eq!(graph.max_depth(), 2);
+
+        let find = |name: &str, depth: usize| {
+            graph
+                .frames
+                .iter()
+                .position(|f| f.name == name && f.depth == depth)
+                .ok_or(format!("missing frame {name} at depth {depth}"))
+        };
+        let query = &graph.frames[find("query", 1)?];
+        assert_eq!(query.count, 2);
+        assert_eq!(query.total_nanos, 40);
+
+        // children are ordered by name: fetch, then query
+        let fetch = &graph.frames[find("fetch", 1)?];
+        assert_eq!(fetch.offset_nanos, 0);
+        assert_eq!(fetch.total_nanos, 30);
+        assert_eq!(query.offset_nanos, 30);
+
+        let nested = find("query", 2)?;
+        assert_eq!(graph.frames[nested].total_nanos, 10);
+        assert_eq!(graph.path_to(nested).len(), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn merges_across_traces() {
+        let one = Trace::new(
+            span("root", "GET /", None, 0, 100),
+            vec![span("q", "query", Some("root"), 0, 40)],
+        );
+        let two = Trace::new(span("root", "GET /", None, 0, 50), vec![]);
+        let other = Trace::new(span("root", "POST /", None, 0, 10), vec![]);
+
+        let graph = super::flame_graph([&one, &two, &other]);
+        assert_eq!(graph.traces, 3);
+        assert_eq!(graph.total_nanos(), 160);
+        assert_eq!(
+            graph
+                .roots
+                .iter()
+                .map(|&root| (graph.frames[root].name.as_str(), graph.frames[root].count))
+                .collect::<Vec<_>>(),
+            vec![("GET /", 2), ("POST /", 1)]
+        );
+    }
+}
@@ -1,11 +1,22 @@
 use std::{
     collections::{BTreeMap, HashMap},
-    io::Read,
+    io::{Read, Write},
+    ops::Range,
     path::Path,
 };
 
 pub mod collector;
+pub mod critical_path;
+pub mod diff;
+pub mod flame_graph;
 pub mod otel;
+pub mod query;
+pub mod retention;
+pub mod service_graph;
+pub mod stats;
+pub mod store;
+#[cfg(test)]
+mod test_util;
 
 pub mod proto {
     include!(concat!(env!("OUT_DIR"), "/_includes.rs"));
@@ -13,7 +24,9 @@ pub mod proto {
 
 use tracing::error;
 
-use crate::proto::opentelemetry::proto::trace::v1::Span as RawSpan;
+use crate::proto::opentelemetry::proto::trace::v1::{
+    span::SpanKind as RawSpanKind, status::StatusCode, Span as RawSpan,
+};
 
 pub fn parse_file(file_path: &Path) -> Result<Vec<Span>, String> {
     let mut contents = String::new();
@@ -35,6 +48,31 @@ pub fn parse_file(file_path: &Path) -> Result<Vec<Span>, String> {
         .collect())
 }
 
+/// Write `spans` to `file_path` in the format read by [`parse_file`],
+/// one JSON object per line.
+pub fn write_file<'a>(
+    file_path: &Path,
+    spans: impl IntoIterator<Item = &'a Span>,
+) -> Result<(), String> {
+    let mut contents = String::new();
+    for span in spans {
+        push_span_line(&mut contents, span)?;
+    }
+    std::fs::File::create(file_path)
+        .and_then(|mut f| f.write_all(contents.as_bytes()))
+        .map_err(|e| e.to_string())
+}
+
+/// Append `span` to `contents` as a line of the format read by
+/// [`parse_file`], returning the length of the line without its
+/// newline.
+pub(crate) fn push_span_line(contents: &mut String, span: &Span) -> Result<usize, String> {
+    let line = serde_json::to_string(&otel::Span::from(span)).map_err(|e| e.to_string())?;
+    contents.push_str(&line);
+    contents.push('\n');
+    Ok(line.len())
+}
+
 pub fn build_traces(spans: Vec<Span>) -> Result<Vec<Trace>, String> {
     let (roots, rest): (Vec<Span>, Vec<Span>) =
         spans.into_iter().partition(|s| s.parent_id.is_none());
@@ -54,17 +92,81 @@ pub fn build_traces(spans: Vec<Span>) -> Result<Vec<Trace>, String> {
     Ok(traces)
 }
 
+/// Format a nanosecond duration using the largest unit (ns, µs, ms
+/// or s) that keeps the value at or above one.
+#[must_use]
+pub fn format_duration(nanos: i64) -> String {
+    const UNITS: [(&str, f64); 3] = [("µs", 1e3), ("ms", 1e6), ("s", 1e9)];
+    if nanos.unsigned_abs() < 1_000 {
+        return format!("{nanos}ns");
+    }
+    #[allow(clippy::cast_precision_loss)]
+    let value = nanos as f64;
+    // the unit is picked after rounding to two decimals, so 999_999ns
+    // is "1.00ms" rather than "1000.00µs"
+    let (unit, scale) = UNITS
+        .into_iter()
+        .find(|&(_, scale)| (value.abs() / scale * 100.0).round() < 100_000.0)
+        .unwrap_or(UNITS[2]);
+    format!("{:.2}{unit}", value / scale)
+}
+
+/// Role of a [`Span`] in the call it describes. Values match the
+/// OTLP enumeration.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SpanKind {
+    #[default]
+    Unspecified,
+    Internal,
+    Server,
+    Client,
+    Producer,
+    Consumer,
+}
+
+impl From<i32> for SpanKind {
+    fn from(value: i32) -> Self {
+        match RawSpanKind::from_i32(value) {
+            Some(RawSpanKind::Internal) => Self::Internal,
+            Some(RawSpanKind::Server) => Self::Server,
+            Some(RawSpanKind::Client) => Self::Client,
+            Some(RawSpanKind::Producer) => Self::Producer,
+            Some(RawSpanKind::Consumer) => Self::Consumer,
+            Some(RawSpanKind::Unspecified) | None => Self::Unspecified,
+        }
+    }
+}
+
+impl From<SpanKind> for i32 {
+    fn from(value: SpanKind) -> Self {
+        let raw = match value {
+            SpanKind::Unspecified => RawSpanKind::Unspecified,
+            SpanKind::Internal => RawSpanKind::Internal,
+            SpanKind::Server => RawSpanKind::Server,
+            SpanKind::Client => RawSpanKind::Client,
+            SpanKind::Producer => RawSpanKind::Producer,
+            SpanKind::Consumer => RawSpanKind::Consumer,
+        };
+        raw as i32
+    }
+}
+
 #[derive(Debug, Default, Clone)]
 pub struct Span {
     pub id: String,
     pub name: String,
+    pub kind: SpanKind,
     pub start: chrono::DateTime<chrono::Utc>,
 
-    /// Microsecond relative offset from beginning of root span.
-    pub offset_micros: i64,
+    /// Nanosecond relative offset from beginning of root span.
+    pub offset_nanos: i64,
+
+    /// Nanosecond duration of span.
+    pub duration_nanos: i64,
 
-    /// Microsecond duration of span.
-    pub duration_micros: i64,
+    /// Nanosecond duration of span not covered by any of its
+    /// children. Calculated by [`Trace::new`].
+    pub self_nanos: i64,
 
     /// Depth within [`Trace`].
     pub level: usize,
@@ -76,6 +178,50 @@ pub struct Span {
 }
 
 impl Span {
+    /// `service.name` resource attribute, if reported.
+    #[must_use]
+    pub fn service_name(&self) -> Option<&str> {
+        self.metadata.get("service.name").map(String::as_str)
+    }
+
+    /// Span reported an error status.
+    #[must_use]
+    pub fn is_error(&self) -> bool {
+        self.metadata
+            .get("status.code")
+            .is_some_and(|code| code == "Error")
+    }
+
+    /// Offset of the end of the span from the beginning of the root
+    /// span, in nanoseconds.
+    #[must_use]
+    pub fn end_offset_nanos(&self) -> i64 {
+        self.offset_nanos + self.duration_nanos
+    }
+
+    /// Case-insensitive search over the span's name, IDs, and
+    /// attribute and metadata keys and values. A query of the form
+    /// `key=value` only matches an attribute or metadata entry whose
+    /// key and value contain the respective halves.
+    #[must_use]
+    pub fn matches_text(&self, query: &str) -> bool {
+        let query = query.trim().to_lowercase();
+        if query.is_empty() {
+            return false;
+        }
+        let contains = |text: &str, part: &str| text.to_lowercase().contains(part);
+        let mut entries = self.attributes.iter().chain(self.metadata.iter());
+        if let Some((key, value)) = query.split_once('=') {
+            let (key, value) = (key.trim(), value.trim());
+            return entries.any(|(k, v)| contains(k, key) && contains(v, value));
+        }
+        [&self.name, &self.id, &self.trace_id]
+            .into_iter()
+            .chain(&self.parent_id)
+            .any(|text| contains(text, &query))
+            || entries.any(|(k, v)| contains(k, &query) || contains(v, &query))
+    }
+
     pub(crate) fn new(
         raw: RawSpan,
         attributes: BTreeMap<String, String>,
@@ -128,12 +274,27 @@ impl Span {
 
         let mut metadata = resource_attributes;
         metadata.extend(instrument_attributes);
+        if let Some(status) = &raw.status {
+            metadata.insert(
+                "status.code".into(),
+                match status.code() {
+                    StatusCode::Unset => "-",
+                    StatusCode::Ok => "Ok",
+                    StatusCode::Error => "Error",
+                }
+                .into(),
+            );
+            metadata.insert("status.description".into(), status.message.clone());
+        }
 
         Ok(Self {
             id,
             name: raw.name.clone(),
+            kind: raw.kind.into(),
             start: chrono::DateTime::<chrono::Utc>::from_naive_utc_and_offset(start, chrono::Utc),
-            duration_micros: end.timestamp_micros() - start.timestamp_micros(),
+            duration_nanos: (end - start)
+                .num_nanoseconds()
+                .ok_or(format!("invalid duration for span {}", raw.name))?,
             trace_id,
             parent_id,
             attributes,
@@ -147,10 +308,14 @@ impl Span {
 pub struct Trace {
     pub id: String,
     pub spans: Vec<Span>,
+    /// When the trace was first received. [`Trace::new`] sets this to
+    /// now, so callers rebuilding a known trace carry it over.
+    pub arrived: chrono::DateTime<chrono::Utc>,
 
     /// Map from parent span to children
-    #[allow(dead_code)]
     connections: HashMap<String, Vec<usize>>,
+    /// Index of each span's parent, `None` for the root.
+    parents: Vec<Option<usize>>,
 }
 
 impl Trace {
@@ -176,7 +341,9 @@ impl Trace {
                         }
                     })
                     .collect::<Vec<_>>();
-                children.sort_by_key(|child| child.start);
+                // ties broken by id so siblings keep their order
+                // when the trace is rebuilt
+                children.sort_by(|l, r| l.start.cmp(&r.start).then_with(|| l.id.cmp(&r.id)));
 
                 for mut child in children {
                     let id = child.id.clone();
@@ -197,8 +364,8 @@ impl Trace {
         let descendants = descendants
             .into_iter()
             .map(|mut span| {
-                span.offset_micros = (span.start - root.start)
-                    .num_microseconds()
+                span.offset_nanos = (span.start - root.start)
+                    .num_nanoseconds()
                     .unwrap_or_default();
                 (span.id.clone(), span)
             })
@@ -222,15 +389,118 @@ impl Trace {
                 .iter()
                 .enumerate()
                 .fold(HashMap::new(), |mut acc, (i, span)| {
-                    acc.entry(span.id.clone()).or_default().push(i);
+                    if let Some(parent_id) = &span.parent_id {
+                        acc.entry(parent_id.clone()).or_default().push(i);
+                    }
                     acc
                 });
 
-        Trace {
+        // spans are in pre-order, so the path from the root to each
+        // span is the last span seen at every level above it
+        let mut path = Vec::new();
+        let parents = descendants
+            .iter()
+            .enumerate()
+            .map(|(i, span)| {
+                path.truncate(span.level);
+                let parent = path.last().copied();
+                path.push(i);
+                parent
+            })
+            .collect();
+
+        let mut trace = Trace {
             id: root.trace_id,
             spans: descendants,
+            arrived: chrono::Utc::now(),
             connections,
+            parents,
+        };
+        for i in 0..trace.spans.len() {
+            trace.spans[i].self_nanos = trace.exclusive_nanos(i);
         }
+        trace
+    }
+
+    /// Duration of the span at `idx` minus the union of its
+    /// children's runtime, clamped to the span's own bounds so that
+    /// overlapping or async children aren't counted twice.
+    fn exclusive_nanos(&self, idx: usize) -> i64 {
+        let span = &self.spans[idx];
+        let mut intervals = self
+            .children(idx)
+            .iter()
+            .map(|&child| {
+                let child = &self.spans[child];
+                (
+                    child.offset_nanos.max(span.offset_nanos),
+                    child.end_offset_nanos().min(span.end_offset_nanos()),
+                )
+            })
+            .filter(|(start, end)| start < end)
+            .collect::<Vec<_>>();
+        intervals.sort_unstable();
+
+        let mut covered = 0;
+        let mut current: Option<(i64, i64)> = None;
+        for (start, end) in intervals {
+            current = match current {
+                Some((cur_start, cur_end)) if start <= cur_end => {
+                    Some((cur_start, cur_end.max(end)))
+                }
+                Some((cur_start, cur_end)) => {
+                    covered += cur_end - cur_start;
+                    Some((start, end))
+                }
+                None => Some((start, end)),
+            };
+        }
+        if let Some((start, end)) = current {
+            covered += end - start;
+        }
+        (span.duration_nanos - covered).max(0)
+    }
+
+    /// Indices of the direct children of the span at `idx`, ordered
+    /// by start time.
+    #[must_use]
+    pub fn children(&self, idx: usize) -> &[usize] {
+        self.spans
+            .get(idx)
+            .and_then(|span| self.connections.get(&span.id))
+            .map_or(&[], Vec::as_slice)
+    }
+
+    /// Index of the span with ID `id`, which, unlike the index,
+    /// stays the same when the trace is rebuilt with new spans.
+    #[must_use]
+    pub fn span_index(&self, id: &str) -> Option<usize> {
+        self.spans.iter().position(|span| span.id == id)
+    }
+
+    /// Indices of every descendant of the span at `idx`. Spans are
+    /// stored in pre-order, so descendants are contiguous.
+    #[must_use]
+    pub fn descendants(&self, idx: usize) -> Range<usize> {
+        let level = self.spans[idx].level;
+        let end = self.spans[idx + 1..]
+            .iter()
+            .position(|span| span.level <= level)
+            .map_or(self.spans.len(), |offset| idx + 1 + offset);
+        idx + 1..end
+    }
+
+    /// Wall-clock time at `offset_nanos` from the beginning of the
+    /// root span.
+    #[must_use]
+    pub fn timestamp_at(&self, offset_nanos: i64) -> chrono::DateTime<chrono::Utc> {
+        self.spans[0].start + chrono::Duration::nanoseconds(offset_nanos)
+    }
+
+    /// Index of the parent of the span at `idx`, if it isn't the root.
+    #[must_use]
+    pub fn parent(&self, idx: usize) -> Option<usize> {
+        self.parents[idx]
     }
 }
 
@@ -270,4 +540,202 @@ mod tests {
         assert_eq!(traces[1].id, "two".to_string());
         Ok(())
     }
+
+    #[test]
+    fn offsets_keep_nanosecond_precision() {
+        let start = chrono::Utc::now();
+        let root = crate::Span {
+            trace_id: "one".to_string(),
+            id: "root".to_string(),
+            start,
+            duration_nanos: 1_500,
+            ..crate::Span::default()
+        };
+        let child = crate::Span {
+            trace_id: "one".to_string(),
+            id: "child".to_string(),
+            parent_id: Some("root".to_string()),
+            start: start + chrono::Duration::nanoseconds(250),
+            duration_nanos: 400,
+            ..crate::Span::default()
+        };
+        let trace = crate::Trace::new(root, vec![child]);
+        assert_eq!(trace.spans[1].offset_nanos, 250);
+        assert_eq!(trace.spans[1].duration_nanos, 400);
+    }
+
+    #[test]
+    fn self_time_excludes_overlapping_children() {
+        let start = chrono::Utc::now();
+        let span = |id: &str, parent: Option<&str>, offset: i64, duration: i64| crate::Span {
+            trace_id: "one".to_string(),
+            id: id.to_string(),
+            parent_id: parent.map(String::from),
+            start: start + chrono::Duration::nanoseconds(offset),
+            duration_nanos: duration,
+            ..crate::Span::default()
+        };
+        let trace = crate::Trace::new(
+            span("root", None, 0, 100),
+            vec![
+                span("a", Some("root"), 10, 30),
+                span("b", Some("root"), 20, 30),
+                span("async", Some("root"), 90, 50),
+                span("a_child", Some("a"), 15, 10),
+            ],
+        );
+        let self_time = |id: &str| {
+            trace
+                .spans
+                .iter()
+                .find(|s| s.id == id)
+                .map(|s| s.self_nanos)
+                .unwrap_or_default()
+        };
+        // root is covered by a∪b (10..50) and async (90..100)
+        assert_eq!(self_time("root"), 50);
+        assert_eq!(self_time("a"), 20);
+        assert_eq!(self_time("b"), 30);
+        assert_eq!(self_time("async"), 50);
+    }
+
+    #[test]
+    fn siblings_with_equal_starts_are_ordered_by_id() {
+        let span = |id: &str, parent: Option<&str>| crate::Span {
+            trace_id: "one".to_string(),
+            id: id.to_string(),
+            parent_id: parent.map(String::from),
+            ..crate::Span::default()
+        };
+        let ids =
+            |trace: &crate::Trace| trace.spans.iter().map(|s| s.id.clone()).collect::<Vec<_>>();
+        let trace = crate::Trace::new(
+            span("root", None),
+            vec![span("b", Some("root")), span("a", Some("root"))],
+        );
+        let reordered = crate::Trace::new(
+            span("root", None),
+            vec![span("a", Some("root")), span("b", Some("root"))],
+        );
+        assert_eq!(ids(&trace), vec!["root", "a", "b"]);
+        assert_eq!(ids(&trace), ids(&reordered));
+    }
+
+    #[test]
+    fn descendants_and_parent() {
+        let span = |id: &str, parent: Option<&str>| crate::Span {
+            trace_id: "one".to_string(),
+            id: id.to_string(),
+            parent_id: parent.map(String::from),
+            ..crate::Span::default()
+        };
+        let trace = crate::Trace::new(
+            span("root", None),
+            vec![
+                span("a", Some("root")),
+                span("a_child", Some("a")),
+                span("b", Some("root")),
+            ],
+        );
+        let ids = trace
+            .spans
+            .iter()
+            .map(|s| s.id.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec!["root", "a", "a_child", "b"]);
+        assert_eq!(trace.descendants(0), 1..4);
+        assert_eq!(trace.descendants(1), 2..3);
+        assert_eq!(trace.descendants(3), 4..4);
+        assert_eq!(trace.parent(0), None);
+        assert_eq!(trace.parent(2), Some(1));
+        assert_eq!(trace.parent(3), Some(0));
+        assert_eq!(trace.span_index("a_child"), Some(2));
+        assert_eq!(trace.span_index("missing"), None);
+    }
+
+    #[test]
+    fn matches_text() {
+        let mut span = crate::Span {
+            id: "abc123".to_string(),
+            name: "GET /users".to_string(),
+            ..crate::Span::default()
+        };
+        span.attributes
+            .insert("user_id".to_string(), "42".to_string());
+        assert!(span.matches_text("get /USERS"));
+        assert!(span.matches_text("abc"));
+        assert!(span.matches_text("user_id"));
+        assert!(span.matches_text("user_id=42"));
+        assert!(span.matches_text("user_id = 42"));
+        assert!(!span.matches_text("user_id=7"));
+        assert!(!span.matches_text("missing"));
+        assert!(!span.matches_text(""));
+    }
+
+    #[test]
+    fn write_file_round_trips() -> Result<(), String> {
+        let start = chrono::Utc::now();
+        let mut root = crate::Span {
+            trace_id: "0af7651916cd43dd8448eb211c80319c".to_string(),
+            id: "b7ad6b7169203331".to_string(),
+            name: "GET /users".to_string(),
+            kind: crate::SpanKind::Server,
+            start,
+            duration_nanos: 1_500,
+            ..crate::Span::default()
+        };
+        root.attributes
+            .insert("http.status_code".to_string(), "200".to_string());
+        root.attributes
+            .insert("http.route".to_string(), "/users".to_string());
+        root.attributes
+            .insert("user.id".to_string(), "007".to_string());
+        root.metadata
+            .insert("service.name".to_string(), "api".to_string());
+        root.metadata
+            .insert("status.code".to_string(), "Error".to_string());
+        let child = crate::Span {
+            trace_id: root.trace_id.clone(),
+            id: "00f067aa0ba902b7".to_string(),
+            parent_id: Some(root.id.clone()),
+            start: start + chrono::Duration::nanoseconds(250),
+            duration_nanos: 400,
+            ..crate::Span::default()
+        };
+
+        let path = std::env::temp_dir().join(format!("write_file_{}.txt", std::process::id()));
+        super::write_file(&path, [&root, &child])?;
+        let spans = super::parse_file(&path);
+        let _ = std::fs::remove_file(&path);
+        let spans = spans?;
+
+        assert_eq!(spans.len(), 2);
+        assert_eq!(spans[0].id, root.id);
+        assert_eq!(spans[0].parent_id, None);
+        assert_eq!(spans[0].kind, crate::SpanKind::Server);
+        assert_eq!(spans[0].start, start);
+        assert_eq!(spans[0].duration_nanos, 1_500);
+        assert_eq!(spans[0].attributes, root.attributes);
+        assert_eq!(spans[0].service_name(), Some("api"));
+        assert!(spans[0].is_error());
+        assert_eq!(spans[1].parent_id, Some(root.id));
+        assert_eq!(spans[1].duration_nanos, 400);
+        assert_eq!(
+            spans[1].metadata.get("status.code").map(String::as_str),
+            Some("-")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn format_duration() {
+        assert_eq!(super::format_duration(0), "0ns");
+        assert_eq!(super::format_duration(999), "999ns");
+        assert_eq!(super::format_duration(1_500), "1.50µs");
+        assert_eq!(super::format_duration(2_346_000), "2.35ms");
+        assert_eq!(super::format_duration(30_000_000_000), "30.00s");
+        assert_eq!(super::format_duration(999_999), "1.00ms");
+        assert_eq!(super::format_duration(999_994), "999.99µs");
+        assert_eq!(super::format_duration(-999_999_999), "-1.00s");
+    }
 }
@@ -2,16 +2,18 @@
 
 use std::collections::BTreeMap;
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 /// Span as represented in tracing stream
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct Span {
     pub name: String,
     #[serde(rename = "SpanContext")]
     pub context: SpanContext,
     pub parent: SpanContext,
+    #[serde(rename = "SpanKind", default)]
+    pub kind: i32,
     #[serde(rename = "StartTime")]
     pub start: chrono::DateTime<chrono::Utc>,
     #[serde(rename = "EndTime")]
@@ -38,6 +40,7 @@ impl From<Span> for crate::Span {
                     .into_iter()
                     .map(|SpanAttribute { key, value }| match value {
                         SpanAttributeValue::Int64 { value } => (key, value.to_string()),
+                        SpanAttributeValue::String { value } => (key, value),
                     })
                     .collect()
             })
@@ -64,9 +67,10 @@ impl From<Span> for crate::Span {
         Self {
             id: value.context.span_id.clone(),
             name: value.name.clone(),
+            kind: value.kind.into(),
             start: value.start,
-            duration_micros: (value.end - value.start)
-                .num_microseconds()
+            duration_nanos: (value.end - value.start)
+                .num_nanoseconds()
                 .unwrap_or_default(),
             trace_id: value.context.trace_id,
             parent_id,
@@ -77,6 +81,77 @@ impl From<Span> for crate::Span {
     }
 }
 
+impl From<&crate::Span> for Span {
+    fn from(value: &crate::Span) -> Self {
+        let metadata = |key: &str| value.metadata.get(key).cloned().unwrap_or_default();
+        let parent = match &value.parent_id {
+            Some(parent_id) => SpanContext {
+                trace_id: value.trace_id.clone(),
+                span_id: parent_id.clone(),
+            },
+            None => SpanContext {
+                trace_id: "0".repeat(32),
+                span_id: "0".repeat(16),
+            },
+        };
+        let attributes = value
+            .attributes
+            .iter()
+            .map(|(key, value)| SpanAttribute {
+                key: key.clone(),
+                // only written as a number if it reads back the same,
+                // so "007" or "+1" stay as they were
+                value: match value.parse::<i64>() {
+                    Ok(number) if number.to_string() == *value => {
+                        SpanAttributeValue::Int64 { value: number }
+                    }
+                    _ => SpanAttributeValue::String {
+                        value: value.clone(),
+                    },
+                },
+            })
+            .collect();
+        let resource = value
+            .metadata
+            .iter()
+            .filter(|(key, _)| !key.starts_with("status.") && !key.starts_with("library."))
+            .map(|(key, value)| Resource {
+                key: key.clone(),
+                value: ResourceValue::String {
+                    value: value.clone(),
+                },
+            })
+            .collect();
+        let code = metadata("status.code");
+        Self {
+            name: value.name.clone(),
+            context: SpanContext {
+                trace_id: value.trace_id.clone(),
+                span_id: value.id.clone(),
+            },
+            parent,
+            kind: value.kind.into(),
+            start: value.start,
+            end: value.start + chrono::Duration::nanoseconds(value.duration_nanos),
+            attributes: Some(attributes),
+            status: Status {
+                code: if code.is_empty() || code == "-" {
+                    "Unset".into()
+                } else {
+                    code
+                },
+                description: metadata("status.description"),
+            },
+            resource,
+            library: Library {
+                name: metadata("library.name"),
+                version: metadata("library.version"),
+                schema_url: metadata("library.schema_url"),
+            },
+        }
+    }
+}
+
 impl Span {
     /// Is current `RawSpan` the root of a trace
     #[must_use]
@@ -85,28 +160,28 @@ impl Span {
     }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 #[serde(rename_all = "PascalCase")]
 struct Status {
     code: String,
     description: String,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 #[serde(rename_all = "PascalCase")]
 struct Resource {
     key: String,
     value: ResourceValue,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 #[serde(rename_all = "UPPERCASE", tag = "Type")]
 enum ResourceValue {
     #[serde(rename_all = "PascalCase")]
     String { value: String },
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 #[serde(rename_all = "PascalCase")]
 struct Library {
     name: String,
@@ -115,7 +190,7 @@ struct Library {
     schema_url: String,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct SpanContext {
     #[serde(rename = "TraceID")]
@@ -124,16 +199,18 @@ pub struct SpanContext {
     pub span_id: String,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 #[serde(rename_all = "PascalCase")]
 struct SpanAttribute {
     key: String,
     value: SpanAttributeValue,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 #[serde(rename_all = "UPPERCASE", tag = "Type")]
 enum SpanAttributeValue {
     #[serde(rename_all = "PascalCase")]
     Int64 { value: i64 },
+    #[serde(rename_all = "PascalCase")]
+    String { value: String },
 }
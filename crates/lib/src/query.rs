@@ -0,0 +1,616 @@
+//! Query language for filtering traces, for example
+//! `service.name = "checkout" AND duration > 200ms AND has(error)`.
+//!
+//! A trace matches when any one of its spans satisfies the whole
+//! query. Fields refer to that span:
+//!
+//! - `name`, `duration`, `kind`, `status`, `error` and `span.id`
+//! - `trace.id`, and `trace.duration` (duration of the root span)
+//! - `span.<key>`: the span attribute `<key>`
+//! - `resource.<key>`: the resource attribute `<key>`
+//! - any other key: the span attribute, falling back to the resource
+//!   attribute, so `service.name` works as expected
+//!
+//! Comparisons are `=`, `!=`, `>`, `>=`, `<`, `<=` and `~`
+//! (case-insensitive contains). Values are quoted strings, bare words,
+//! numbers, or durations with a unit (`ns`, `us`, `ms`, `s`, `m`,
+//! `h`). `has(field)` checks a field is present (or for `error`, that
+//! the span failed). Terms combine with `AND`, `OR`, `NOT` and
+//! parentheses; adjacent terms are implicitly `AND`ed. A term without
+//! a comparison is a free-text search, see [`Span::matches_text`].
+
+use std::{borrow::Cow, cmp::Ordering};
+
+use crate::{Span, Trace};
+
+/// Parsed query. The default, empty query matches every trace.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Query {
+    expr: Option<Expr>,
+}
+
+impl Query {
+    /// Parse `source`. Errors name the column (counting from 1) where
+    /// parsing failed.
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let tokens = tokenize(source)?;
+        if tokens.is_empty() {
+            return Ok(Self::default());
+        }
+        let mut parser = Parser {
+            tokens,
+            pos: 0,
+            len: source.chars().count(),
+        };
+        let expr = parser.or()?;
+        if let Some(token) = parser.tokens.get(parser.pos) {
+            return Err(format!(
+                "unexpected `{}` at column {}",
+                token.text,
+                token.column + 1
+            ));
+        }
+        Ok(Self { expr: Some(expr) })
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.expr.is_none()
+    }
+
+    /// Whether any span of `trace` satisfies the query.
+    #[must_use]
+    pub fn matches(&self, trace: &Trace) -> bool {
+        match &self.expr {
+            Some(expr) => trace.spans.iter().any(|span| expr.matches(trace, span)),
+            None => true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Has(Field),
+    Compare(Field, Op, Value),
+    /// Free-text search.
+    Text(String),
+}
+
+impl Expr {
+    fn matches(&self, trace: &Trace, span: &Span) -> bool {
+        match self {
+            Self::And(l, r) => l.matches(trace, span) && r.matches(trace, span),
+            Self::Or(l, r) => l.matches(trace, span) || r.matches(trace, span),
+            Self::Not(expr) => !expr.matches(trace, span),
+            Self::Has(Field::Error) => span.is_error(),
+            Self::Has(field) => field.value(trace, span).is_some(),
+            Self::Compare(field, op, expected) => field
+                .value(trace, span)
+                .is_some_and(|actual| compare(&actual, *op, expected)),
+            Self::Text(text) => span.matches_text(text),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Field {
+    Name,
+    Duration,
+    TraceDuration,
+    Kind,
+    Status,
+    Error,
+    SpanId,
+    TraceId,
+    Attribute(String),
+    Resource(String),
+    /// Span attribute, falling back to resource attribute.
+    Any(String),
+}
+
+impl Field {
+    fn parse(word: &str) -> Self {
+        match word.to_lowercase().as_str() {
+            "name" => Self::Name,
+            "duration" => Self::Duration,
+            "trace.duration" => Self::TraceDuration,
+            "kind" => Self::Kind,
+            "status" | "status.code" => Self::Status,
+            "error" => Self::Error,
+            "span.id" => Self::SpanId,
+            "trace.id" => Self::TraceId,
+            _ => {
+                // keys keep their case, but prefixes ignore it like
+                // the keywords above
+                let strip_prefix = |prefix: &str| {
+                    word.get(..prefix.len())
+                        .filter(|head| head.eq_ignore_ascii_case(prefix))
+                        .map(|_| &word[prefix.len()..])
+                };
+                if let Some(key) = strip_prefix("span.") {
+                    Self::Attribute(key.to_string())
+                } else if let Some(key) = strip_prefix("resource.") {
+                    Self::Resource(key.to_string())
+                } else {
+                    Self::Any(word.to_string())
+                }
+            }
+        }
+    }
+
+    fn is_duration(&self) -> bool {
+        matches!(self, Self::Duration | Self::TraceDuration)
+    }
+
+    /// Value of the field for `span`, if it has one. Kind and status
+    /// are lowercased so comparisons ignore case.
+    fn value<'a>(&self, trace: &'a Trace, span: &'a Span) -> Option<Actual<'a>> {
+        let text = |text: &'a String| Actual::Text(Cow::Borrowed(text));
+        match self {
+            Self::Name => Some(text(&span.name)),
+            Self::Duration => Some(Actual::Nanos(span.duration_nanos)),
+            Self::TraceDuration => trace
+                .spans
+                .first()
+                .map(|root| Actual::Nanos(root.duration_nanos)),
+            Self::Kind => Some(Actual::Text(Cow::Owned(
+                format!("{:?}", span.kind).to_lowercase(),
+            ))),
+            Self::Status => span
+                .metadata
+                .get("status.code")
+                .map(|code| Actual::Text(Cow::Owned(code.to_lowercase()))),
+            Self::Error => Some(Actual::Text(Cow::Borrowed(if span.is_error() {
+                "true"
+            } else {
+                "false"
+            }))),
+            Self::SpanId => Some(text(&span.id)),
+            Self::TraceId => Some(text(&span.trace_id)),
+            Self::Attribute(key) => span.attributes.get(key).map(text),
+            Self::Resource(key) => span.metadata.get(key).map(text),
+            Self::Any(key) => span
+                .attributes
+                .get(key)
+                .or_else(|| span.metadata.get(key))
+                .map(text),
+        }
+    }
+}
+
+/// Value of a [`Field`] for a particular span.
+enum Actual<'a> {
+    Text(Cow<'a, str>),
+    Nanos(i64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Eq,
+    Ne,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+    /// Case-insensitive substring.
+    Contains,
+}
+
+impl Op {
+    fn accepts(self, ordering: Ordering) -> bool {
+        match self {
+            Self::Eq => ordering == Ordering::Equal,
+            Self::Ne => ordering != Ordering::Equal,
+            Self::Gt => ordering == Ordering::Greater,
+            Self::Ge => ordering != Ordering::Less,
+            Self::Lt => ordering == Ordering::Less,
+            Self::Le => ordering != Ordering::Greater,
+            Self::Contains => false,
+        }
+    }
+}
+
+/// Value a field is compared against.
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    /// Lowercased when compared with [`Op::Contains`].
+    Text(String),
+    Number(f64),
+    Nanos(i64),
+}
+
+fn compare(actual: &Actual, op: Op, expected: &Value) -> bool {
+    match (actual, expected) {
+        (Actual::Nanos(actual), Value::Nanos(expected)) => op.accepts(actual.cmp(expected)),
+        (Actual::Text(actual), Value::Text(expected)) => match op {
+            Op::Contains => actual.to_lowercase().contains(expected),
+            op => op.accepts(actual.as_ref().cmp(expected)),
+        },
+        // attributes are stored as text: compare numerically when
+        // they parse as a number
+        (Actual::Text(actual), Value::Number(expected)) => actual
+            .parse::<f64>()
+            .ok()
+            .and_then(|actual| actual.partial_cmp(expected))
+            .is_some_and(|ordering| op.accepts(ordering)),
+        _ => false,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum TokenKind {
+    LParen,
+    RParen,
+    Op(Op),
+    /// Field name, keyword or bare value.
+    Word,
+    /// Quoted string, unescaped.
+    Quoted(String),
+    Number(f64),
+    Nanos(i64),
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: TokenKind,
+    /// Source text of the token.
+    text: String,
+    /// Offset of the token in the source, in characters.
+    column: usize,
+}
+
+/// Nanoseconds per duration unit.
+fn unit_nanos(unit: &str) -> Option<f64> {
+    match unit {
+        "ns" => Some(1.0),
+        "us" | "µs" => Some(1e3),
+        "ms" => Some(1e6),
+        "s" => Some(1e9),
+        "m" => Some(60e9),
+        "h" => Some(3600e9),
+        _ => None,
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '.' | '_' | '-' | '/' | ':' | 'µ')
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+    let chars = source.chars().collect::<Vec<_>>();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let column = i;
+        let c = chars[i];
+        let kind = match c {
+            c if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            '(' => {
+                i += 1;
+                TokenKind::LParen
+            }
+            ')' => {
+                i += 1;
+                TokenKind::RParen
+            }
+            '=' | '!' | '<' | '>' | '~' => {
+                let next = chars.get(i + 1).copied();
+                let (op, len) = match (c, next) {
+                    ('=', Some('=')) => (Op::Eq, 2),
+                    ('=', _) => (Op::Eq, 1),
+                    ('!', Some('=')) => (Op::Ne, 2),
+                    ('<', Some('=')) => (Op::Le, 2),
+                    ('<', _) => (Op::Lt, 1),
+                    ('>', Some('=')) => (Op::Ge, 2),
+                    ('>', _) => (Op::Gt, 1),
+                    ('~', _) => (Op::Contains, 1),
+                    _ => return Err(format!("expected `!=` at column {}", column + 1)),
+                };
+                i += len;
+                TokenKind::Op(op)
+            }
+            '"' | '\'' => {
+                let mut text = String::new();
+                i += 1;
+                loop {
+                    match chars.get(i) {
+                        None => {
+                            return Err(format!("unterminated string at column {}", column + 1))
+                        }
+                        Some('\\') if i + 1 < chars.len() => {
+                            text.push(chars[i + 1]);
+                            i += 2;
+                        }
+                        Some(&end) if end == c => {
+                            i += 1;
+                            break;
+                        }
+                        Some(&other) => {
+                            text.push(other);
+                            i += 1;
+                        }
+                    }
+                }
+                TokenKind::Quoted(text)
+            }
+            c if is_word_char(c) => {
+                while i < chars.len() && is_word_char(chars[i]) {
+                    i += 1;
+                }
+                let word = chars[column..i].iter().collect::<String>();
+                number(&word).unwrap_or(TokenKind::Word)
+            }
+            c => return Err(format!("unexpected `{c}` at column {}", column + 1)),
+        };
+        tokens.push(Token {
+            kind,
+            text: chars[column..i].iter().collect(),
+            column,
+        });
+    }
+    Ok(tokens)
+}
+
+/// `word` as a number or duration, if it is one. Words merely
+/// starting with digits, like hex IDs, are left as words.
+fn number(word: &str) -> Option<TokenKind> {
+    let split = word
+        .find(|c: char| c.is_alphabetic() || c == 'µ')
+        .unwrap_or(word.len());
+    let (value, unit) = word.split_at(split);
+    let value = value.parse::<f64>().ok()?;
+    if unit.is_empty() {
+        return Some(TokenKind::Number(value));
+    }
+    #[allow(clippy::cast_possible_truncation)]
+    unit_nanos(unit).map(|nanos| TokenKind::Nanos((value * nanos).round() as i64))
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    /// Length of the source, in characters, for errors at its end.
+    len: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Result<Token, String> {
+        let token = self.tokens.get(self.pos).cloned().ok_or(format!(
+            "unexpected end of query at column {}",
+            self.len + 1
+        ))?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    /// Consume the next token if it is the (case-insensitive) keyword.
+    fn keyword(&mut self, keyword: &str) -> bool {
+        let found = self.peek().is_some_and(|token| {
+            token.kind == TokenKind::Word && token.text.eq_ignore_ascii_case(keyword)
+        });
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, kind: &TokenKind, expected: &str) -> Result<(), String> {
+        let token = self.next()?;
+        if token.kind == *kind {
+            Ok(())
+        } else {
+            Err(format!(
+                "expected {expected} at column {}, found `{}`",
+                token.column + 1,
+                token.text
+            ))
+        }
+    }
+
+    fn or(&mut self) -> Result<Expr, String> {
+        let mut expr = self.and()?;
+        while self.keyword("or") {
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        let mut expr = self.not()?;
+        loop {
+            if self.keyword("and") {
+                expr = Expr::And(Box::new(expr), Box::new(self.not()?));
+                continue;
+            }
+            // adjacent terms are implicitly ANDed
+            let adjacent = self.peek().is_some_and(|token| match token.kind {
+                TokenKind::RParen => false,
+                TokenKind::Word => !token.text.eq_ignore_ascii_case("or"),
+                _ => true,
+            });
+            if !adjacent {
+                return Ok(expr);
+            }
+            expr = Expr::And(Box::new(expr), Box::new(self.not()?));
+        }
+    }
+
+    fn not(&mut self) -> Result<Expr, String> {
+        if self.keyword("not") {
+            Ok(Expr::Not(Box::new(self.not()?)))
+        } else {
+            self.term()
+        }
+    }
+
+    fn term(&mut self) -> Result<Expr, String> {
+        let token = self.next()?;
+        let is_call = |parser: &Self| {
+            parser
+                .peek()
+                .is_some_and(|next| next.kind == TokenKind::LParen)
+        };
+        match token.kind {
+            TokenKind::LParen => {
+                let expr = self.or()?;
+                self.expect(&TokenKind::RParen, "`)`")?;
+                Ok(expr)
+            }
+            TokenKind::Word if token.text.eq_ignore_ascii_case("has") && is_call(self) => {
+                self.pos += 1;
+                let field = self.next()?;
+                if field.kind != TokenKind::Word {
+                    return Err(format!("expected a field at column {}", field.column + 1));
+                }
+                self.expect(&TokenKind::RParen, "`)`")?;
+                Ok(Expr::Has(Field::parse(&field.text)))
+            }
+            TokenKind::Word => match self.peek().map(|next| next.kind.clone()) {
+                Some(TokenKind::Op(op)) => {
+                    self.pos += 1;
+                    self.comparison(&token, op)
+                }
+                _ => Ok(Expr::Text(token.text)),
+            },
+            TokenKind::Quoted(text) => Ok(Expr::Text(text)),
+            TokenKind::Number(_) | TokenKind::Nanos(_) => Ok(Expr::Text(token.text)),
+            TokenKind::RParen | TokenKind::Op(_) => Err(format!(
+                "expected a field or search term at column {}, found `{}`",
+                token.column + 1,
+                token.text
+            )),
+        }
+    }
+
+    /// Rest of a comparison of `field`, after the operator.
+    fn comparison(&mut self, field: &Token, op: Op) -> Result<Expr, String> {
+        let token = self.next()?;
+        let column = token.column + 1;
+        let field = Field::parse(&field.text);
+        let mut value = match token.kind {
+            TokenKind::Word => Value::Text(token.text.clone()),
+            TokenKind::Quoted(text) => Value::Text(text),
+            TokenKind::Number(number) => Value::Number(number),
+            TokenKind::Nanos(nanos) => Value::Nanos(nanos),
+            TokenKind::LParen | TokenKind::RParen | TokenKind::Op(_) => {
+                return Err(format!(
+                    "expected a value at column {column}, found `{}`",
+                    token.text
+                ))
+            }
+        };
+        if field.is_duration() {
+            if !matches!(value, Value::Nanos(_)) {
+                return Err(format!(
+                    "expected a duration such as 200ms at column {column}"
+                ));
+            }
+            if op == Op::Contains {
+                return Err(format!(
+                    "`~` can't be used with durations, at column {column}"
+                ));
+            }
+        } else if let Value::Nanos(_) = value {
+            return Err(format!(
+                "only `duration` and `trace.duration` can be compared with a duration, at column {column}"
+            ));
+        }
+        if op == Op::Contains || matches!(field, Field::Kind | Field::Status | Field::Error) {
+            // compare kind and status regardless of case; contains
+            // compares text even if it looks like a number
+            let text = match value {
+                Value::Text(text) => text,
+                _ => token.text,
+            };
+            value = Value::Text(text.to_lowercase());
+        }
+        Ok(Expr::Compare(field, op, value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Query;
+    use crate::{
+        test_util::{span, with_service},
+        Trace,
+    };
+
+    fn trace() -> Trace {
+        let span = |id, name, parent, duration| {
+            with_service(span(id, name, parent, 0, duration), "checkout")
+        };
+        let mut payment = span("payment", "POST /pay", Some("root"), 250_000_000);
+        payment
+            .attributes
+            .insert("http.status_code".to_string(), "502".to_string());
+        payment
+            .metadata
+            .insert("status.code".to_string(), "Error".to_string());
+        Trace::new(
+            span("root", "GET /checkout", None, 300_000_000),
+            vec![payment, span("cache", "cache get", Some("root"), 1_000)],
+        )
+    }
+
+    fn matches(query: &str) -> Result<bool, String> {
+        Ok(Query::parse(query)?.matches(&trace()))
+    }
+
+    #[test]
+    fn evaluates_against_each_span() -> Result<(), String> {
+        assert!(matches("")?);
+        assert!(matches(
+            r#"service.name = "checkout" AND duration > 200ms AND span.http.status_code >= 500 AND has(error)"#
+        )?);
+        // no single span is both slow and a cache lookup
+        assert!(!matches("duration > 200ms AND name ~ cache")?);
+        assert!(matches("duration > 200ms OR name ~ cache")?);
+        assert!(matches("name ~ CACHE duration < 2us")?);
+        assert!(matches("trace.duration >= 0.3s status = error")?);
+        assert!(!matches("NOT service.name = checkout")?);
+        assert!(matches(
+            "not (kind = server) and has(span.http.status_code)"
+        )?);
+        assert!(!matches("resource.http.status_code = 502")?);
+        assert!(matches("SPAN.http.status_code = 502")?);
+        assert!(matches("Resource.service.name = checkout")?);
+        assert!(matches("pay")?);
+        assert!(!matches("missing")?);
+        Ok(())
+    }
+
+    #[test]
+    fn reports_syntax_errors_with_column() {
+        assert_eq!(
+            Query::parse("duration > 200"),
+            Err("expected a duration such as 200ms at column 12".to_string())
+        );
+        assert_eq!(
+            Query::parse("name = \"GET"),
+            Err("unterminated string at column 8".to_string())
+        );
+        assert_eq!(
+            Query::parse("(name = a"),
+            Err("unexpected end of query at column 10".to_string())
+        );
+        assert_eq!(
+            Query::parse("name = a )"),
+            Err("unexpected `)` at column 10".to_string())
+        );
+        assert_eq!(
+            Query::parse("name ="),
+            Err("unexpected end of query at column 7".to_string())
+        );
+    }
+}
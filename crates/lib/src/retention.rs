@@ -0,0 +1,184 @@
+//! Limits on how many [`Trace`]s are kept in memory.
+
+use std::mem::size_of;
+
+use crate::{Span, Trace};
+
+/// Limits on the traces kept in memory. `None` leaves that measure
+/// unlimited.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Retention {
+    pub max_traces: Option<usize>,
+    pub max_spans: Option<usize>,
+    /// Limit on [`Usage::bytes`].
+    pub max_bytes: Option<usize>,
+    /// Traces that arrived longer ago than this are dropped, however
+    /// few traces there are. See [`Trace::arrived`].
+    pub max_age: Option<chrono::Duration>,
+}
+
+/// Size of a set of traces.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Usage {
+    pub traces: usize,
+    pub spans: usize,
+    /// Approximate memory used, see [`trace_bytes`].
+    pub bytes: usize,
+}
+
+impl Usage {
+    #[must_use]
+    pub fn of(traces: &[Trace]) -> Self {
+        Self {
+            traces: traces.len(),
+            spans: traces.iter().map(|trace| trace.spans.len()).sum(),
+            bytes: traces.iter().map(trace_bytes).sum(),
+        }
+    }
+}
+
+impl Retention {
+    /// Whether `usage` is over any of the size limits.
+    #[must_use]
+    pub fn is_exceeded(&self, usage: &Usage) -> bool {
+        let over = |limit: Option<usize>, value: usize| limit.is_some_and(|limit| value > limit);
+        over(self.max_traces, usage.traces)
+            || over(self.max_spans, usage.spans)
+            || over(self.max_bytes, usage.bytes)
+    }
+
+    /// Drop traces that arrived more than [`Self::max_age`] before
+    /// `now`, then the earliest added traces until `traces` is within
+    /// the size limits. Traces for which `keep` returns true are never
+    /// dropped, so the limits may still be exceeded. Returns the number
+    /// of traces dropped.
+    pub fn apply(
+        &self,
+        traces: &mut Vec<Trace>,
+        now: chrono::DateTime<chrono::Utc>,
+        keep: impl Fn(&Trace) -> bool,
+    ) -> usize {
+        let mut usage = Usage::of(traces);
+        let before = traces.len();
+        traces.retain(|trace| {
+            let expired = self
+                .max_age
+                .is_some_and(|max_age| now - trace.arrived > max_age);
+            if keep(trace) || !(expired || self.is_exceeded(&usage)) {
+                return true;
+            }
+            usage.traces -= 1;
+            usage.spans -= trace.spans.len();
+            usage.bytes -= trace_bytes(trace);
+            false
+        });
+        before - traces.len()
+    }
+}
+
+/// Approximate memory used by `trace`: its spans, their strings and
+/// the parent/child index, ignoring allocator overhead.
+#[must_use]
+pub fn trace_bytes(trace: &Trace) -> usize {
+    let connections = trace
+        .connections
+        .iter()
+        .map(|(parent, children)| {
+            size_of::<(String, Vec<usize>)>() + parent.len() + children.len() * size_of::<usize>()
+        })
+        .sum::<usize>();
+    size_of::<Trace>()
+        + trace.id.len()
+        + trace.spans.iter().map(span_bytes).sum::<usize>()
+        + connections
+}
+
+/// Approximate memory used by `span`, as counted by [`trace_bytes`].
+#[must_use]
+pub fn span_bytes(span: &Span) -> usize {
+    let entries = span
+        .attributes
+        .iter()
+        .chain(&span.metadata)
+        .map(|(key, value)| size_of::<(String, String)>() + key.len() + value.len())
+        .sum::<usize>();
+    size_of::<Span>()
+        + span.id.len()
+        + span.name.len()
+        + span.trace_id.len()
+        + span.parent_id.as_ref().map_or(0, String::len)
+        + entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Retention, Usage};
+    use crate::{test_util::span, Trace};
+
+    fn trace(id: &str, children: usize) -> Trace {
+        let root_id = format!("{id}_root");
+        let mut root = span(&root_id, "root", None, 0, 1);
+        root.trace_id = id.to_string();
+        let children = (0..children)
+            .map(|n| {
+                let mut child = span(&format!("{id}_{n}"), "child", Some(&root_id), 0, 1);
+                child.trace_id = id.to_string();
+                child
+            })
+            .collect();
+        Trace::new(root, children)
+    }
+
+    fn ids(traces: &[Trace]) -> Vec<&str> {
+        traces.iter().map(|trace| trace.id.as_str()).collect()
+    }
+
+    #[test]
+    fn drops_earliest_traces_over_limits() {
+        let now = chrono::Utc::now();
+        let mut traces = vec![trace("a", 1), trace("b", 3), trace("c", 0)];
+        let retention = Retention {
+            max_traces: Some(2),
+            ..Retention::default()
+        };
+        assert_eq!(retention.apply(&mut traces, now, |_| false), 1);
+        assert_eq!(ids(&traces), vec!["b", "c"]);
+
+        let retention = Retention {
+            max_spans: Some(2),
+            ..Retention::default()
+        };
+        assert_eq!(retention.apply(&mut traces, now, |_| false), 1);
+        assert_eq!(ids(&traces), vec!["c"]);
+
+        let mut traces = vec![trace("a", 1), trace("b", 1)];
+        let retention = Retention {
+            max_bytes: Some(Usage::of(&traces).bytes - 1),
+            ..Retention::default()
+        };
+        assert_eq!(retention.apply(&mut traces, now, |_| false), 1);
+        assert_eq!(ids(&traces), vec!["b"]);
+    }
+
+    #[test]
+    fn keeps_traces_and_drops_expired_ones() {
+        let now = chrono::Utc::now();
+        let mut old = trace("c", 0);
+        old.arrived = now - chrono::Duration::minutes(10);
+        // spans started long ago, but only arrival counts
+        let mut traces = vec![trace("a", 0), trace("b", 0), old];
+        let retention = Retention {
+            max_traces: Some(2),
+            max_age: Some(chrono::Duration::minutes(5)),
+            ..Retention::default()
+        };
+        // "a" is kept, so "b" goes instead; "c" expired despite being
+        // within the count once "b" is gone
+        assert_eq!(
+            retention.apply(&mut traces, now, |trace| trace.id == "a"),
+            2
+        );
+        assert_eq!(ids(&traces), vec!["a"]);
+        assert!(!retention.is_exceeded(&Usage::of(&traces)));
+    }
+}
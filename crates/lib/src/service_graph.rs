@@ -0,0 +1,172 @@
+//! Service dependency graph derived from loaded [`Trace`]s.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{stats::Summary, SpanKind, Trace};
+
+/// Attributes naming the remote side of a client span, in order of
+/// preference. Used when the callee wasn't instrumented.
+const PEER_ATTRIBUTES: [&str; 4] = [
+    "peer.service",
+    "db.system",
+    "server.address",
+    "net.peer.name",
+];
+
+/// A single call from one service to another within a [`Trace`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Call {
+    pub caller: String,
+    pub callee: String,
+    /// Index into [`Trace::spans`] of the span measuring the call.
+    pub span: usize,
+}
+
+impl Trace {
+    /// Calls crossing a service boundary. A call is either a child
+    /// span whose `service.name` differs from its parent's, or a
+    /// client/producer span with no such child that names its peer
+    /// in an attribute (e.g. `peer.service`).
+    #[must_use]
+    pub fn service_calls(&self) -> Vec<Call> {
+        let mut calls = Vec::new();
+        for (idx, span) in self.spans.iter().enumerate() {
+            let Some(service) = span.service_name() else {
+                continue;
+            };
+
+            let mut has_remote_child = false;
+            for &child in self.children(idx) {
+                match self.spans[child].service_name() {
+                    Some(child_service) if child_service != service => {
+                        has_remote_child = true;
+                        calls.push(Call {
+                            caller: service.to_string(),
+                            callee: child_service.to_string(),
+                            span: child,
+                        });
+                    }
+                    _ => {}
+                }
+            }
+
+            if !has_remote_child && matches!(span.kind, SpanKind::Client | SpanKind::Producer) {
+                if let Some(peer) = PEER_ATTRIBUTES
+                    .iter()
+                    .find_map(|key| span.attributes.get(*key))
+                {
+                    calls.push(Call {
+                        caller: service.to_string(),
+                        callee: peer.clone(),
+                        span: idx,
+                    });
+                }
+            }
+        }
+        calls
+    }
+}
+
+/// Aggregated calls between two services.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edge {
+    pub caller: String,
+    pub callee: String,
+    pub count: usize,
+    pub errors: usize,
+    pub latency: Summary,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ServiceGraph {
+    /// Every service seen, including those with no calls, sorted by
+    /// name.
+    pub services: Vec<String>,
+    pub edges: Vec<Edge>,
+}
+
+/// Build a [`ServiceGraph`] from every [`Call`] in `traces`.
+#[must_use]
+pub fn service_graph<'a>(traces: impl IntoIterator<Item = &'a Trace>) -> ServiceGraph {
+    let mut services = BTreeSet::new();
+    let mut edges = BTreeMap::<(String, String), (usize, Vec<i64>)>::new();
+
+    for trace in traces {
+        services.extend(
+            trace
+                .spans
+                .iter()
+                .filter_map(|span| span.service_name().map(String::from)),
+        );
+        for call in trace.service_calls() {
+            let span = &trace.spans[call.span];
+            services.insert(call.callee.clone());
+            let (errors, latencies) = edges.entry((call.caller, call.callee)).or_default();
+            *errors += usize::from(span.is_error());
+            latencies.push(span.duration_nanos);
+        }
+    }
+
+    ServiceGraph {
+        services: services.into_iter().collect(),
+        edges: edges
+            .into_iter()
+            .map(|((caller, callee), (errors, mut latencies))| {
+                latencies.sort_unstable();
+                Edge {
+                    caller,
+                    callee,
+                    count: latencies.len(),
+                    errors,
+                    latency: Summary::from_sorted(&latencies),
+                }
+            })
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        test_util::{span, with_service},
+        Span, SpanKind, Trace,
+    };
+
+    #[test]
+    fn edges_from_parent_child_and_client_spans() {
+        let span = |id, parent, service, kind| Span {
+            kind,
+            ..with_service(span(id, id, parent, 0, 10), service)
+        };
+        let mut db_call = span("query", Some("handler"), "checkout", SpanKind::Client);
+        db_call
+            .attributes
+            .insert("db.system".to_string(), "postgresql".to_string());
+        let trace = Trace::new(
+            span("root", None, "frontend", SpanKind::Server),
+            vec![
+                span("call", Some("root"), "frontend", SpanKind::Client),
+                span("handler", Some("call"), "checkout", SpanKind::Server),
+                db_call,
+            ],
+        );
+
+        let graph = super::service_graph([&trace, &trace]);
+        assert_eq!(
+            graph.services,
+            vec![
+                "checkout".to_string(),
+                "frontend".to_string(),
+                "postgresql".to_string()
+            ]
+        );
+        assert_eq!(
+            graph
+                .edges
+                .iter()
+                .map(|e| (e.caller.as_str(), e.callee.as_str(), e.count))
+                .collect::<Vec<_>>(),
+            vec![("checkout", "postgresql", 2), ("frontend", "checkout", 2)]
+        );
+    }
+}
@@ -0,0 +1,169 @@
+//! Aggregate statistics for operations across many [`Trace`]s.
+
+use std::collections::BTreeMap;
+
+use crate::Trace;
+
+/// Distribution of a set of nanosecond values.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Summary {
+    pub min: i64,
+    pub avg: i64,
+    pub p50: i64,
+    pub p90: i64,
+    pub p99: i64,
+    pub max: i64,
+}
+
+impl Summary {
+    /// Summarize `values`, which must already be sorted ascending.
+    #[must_use]
+    pub fn from_sorted(values: &[i64]) -> Self {
+        if values.is_empty() {
+            return Self::default();
+        }
+        #[allow(clippy::cast_possible_wrap)]
+        let avg = values.iter().sum::<i64>() / values.len() as i64;
+        Self {
+            min: values[0],
+            avg,
+            p50: percentile(values, 50),
+            p90: percentile(values, 90),
+            p99: percentile(values, 99),
+            max: values[values.len() - 1],
+        }
+    }
+}
+
+/// Nearest-rank percentile of `values`, which must be sorted
+/// ascending and non-empty.
+#[must_use]
+pub fn percentile(values: &[i64], p: usize) -> i64 {
+    let rank = (p * values.len()).div_ceil(100);
+    values[rank.clamp(1, values.len()) - 1]
+}
+
+/// Statistics for every span sharing a (`service.name`, span name)
+/// pair.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OperationStats {
+    /// `service.name` of the spans. Empty when not reported.
+    pub service: String,
+    pub name: String,
+    pub count: usize,
+    pub errors: usize,
+    pub duration: Summary,
+    pub self_time: Summary,
+}
+
+impl OperationStats {
+    /// Fraction of spans (0.0..=1.0) that reported an error status.
+    #[must_use]
+    pub fn error_rate(&self) -> f64 {
+        if self.count == 0 {
+            0.0
+        } else {
+            #[allow(clippy::cast_precision_loss)]
+            let rate = self.errors as f64 / self.count as f64;
+            rate
+        }
+    }
+}
+
+/// Aggregate every span in `traces` by (`service.name`, span name).
+/// Results are ordered by service, then name.
+#[must_use]
+pub fn operation_stats<'a>(traces: impl IntoIterator<Item = &'a Trace>) -> Vec<OperationStats> {
+    #[derive(Default)]
+    struct Samples {
+        errors: usize,
+        durations: Vec<i64>,
+        self_times: Vec<i64>,
+    }
+
+    let samples = traces
+        .into_iter()
+        .flat_map(|trace| trace.spans.iter())
+        .fold(BTreeMap::<_, Samples>::new(), |mut m, span| {
+            let key = (
+                span.service_name().unwrap_or_default().to_string(),
+                span.name.clone(),
+            );
+            let samples = m.entry(key).or_default();
+            samples.errors += usize::from(span.is_error());
+            samples.durations.push(span.duration_nanos);
+            samples.self_times.push(span.self_nanos);
+            m
+        });
+
+    samples
+        .into_iter()
+        .map(|((service, name), mut samples)| {
+            samples.durations.sort_unstable();
+            samples.self_times.sort_unstable();
+            OperationStats {
+                service,
+                name,
+                count: samples.durations.len(),
+                errors: samples.errors,
+                duration: Summary::from_sorted(&samples.durations),
+                self_time: Summary::from_sorted(&samples.self_times),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        test_util::{span, with_service},
+        Trace,
+    };
+
+    fn root(trace_id: &str, service: &str, name: &str, duration: i64, error: bool) -> Trace {
+        let mut span = with_service(
+            span(&format!("{trace_id}_root"), name, None, 0, duration),
+            service,
+        );
+        span.trace_id = trace_id.to_string();
+        if error {
+            span.metadata
+                .insert("status.code".to_string(), "Error".to_string());
+        }
+        Trace::new(span, vec![])
+    }
+
+    #[test]
+    fn percentile_nearest_rank() {
+        let values = (1..=100).collect::<Vec<i64>>();
+        assert_eq!(super::percentile(&values, 50), 50);
+        assert_eq!(super::percentile(&values, 90), 90);
+        assert_eq!(super::percentile(&values, 99), 99);
+        assert_eq!(super::percentile(&[7], 99), 7);
+    }
+
+    #[test]
+    fn groups_by_service_and_name() -> Result<(), String> {
+        let traces = vec![
+            root("1", "checkout", "GET /cart", 10, false),
+            root("2", "checkout", "GET /cart", 30, true),
+            root("3", "checkout", "POST /pay", 5, false),
+            root("4", "billing", "GET /cart", 1, false),
+        ];
+        let stats = super::operation_stats(&traces);
+        assert_eq!(stats.len(), 3);
+
+        let cart = stats
+            .iter()
+            .find(|s| s.service == "checkout" && s.name == "GET /cart")
+            .ok_or("missing checkout cart stats")?;
+        assert_eq!(cart.count, 2);
+        assert_eq!(cart.errors, 1);
+        assert!((cart.error_rate() - 0.5).abs() < f64::EPSILON);
+        assert_eq!(cart.duration.min, 10);
+        assert_eq!(cart.duration.avg, 20);
+        assert_eq!(cart.duration.max, 30);
+        assert_eq!(cart.self_time.max, 30);
+        Ok(())
+    }
+}
@@ -0,0 +1,735 @@
+//! Persistent storage for spans, so traces outlive the process.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fs::{File, OpenOptions},
+    io::{Read, Seek, SeekFrom, Write},
+    ops::Bound,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use tracing::error;
+
+use crate::{
+    otel,
+    retention::{Retention, Usage},
+    Span,
+};
+
+/// Segments are closed once they grow past this many bytes.
+const SEGMENT_BYTES: u64 = 64 * 1024 * 1024;
+
+/// Least number of segments a byte limit is spread across, so pruning
+/// deletes a fraction of what is stored at a time.
+const MIN_SEGMENTS: u64 = 8;
+
+/// Span storage that outlives the process, indexed by trace.
+pub trait Store: Send + std::fmt::Debug {
+    /// Persist `spans`.
+    fn append(&mut self, spans: &[Span]) -> Result<(), String>;
+
+    /// Stored traces matching `filter`, most recently started first.
+    fn traces(&self, filter: &Filter) -> Vec<StoredTrace>;
+
+    /// Every stored span of the trace with ID `trace_id`.
+    fn load(&self, trace_id: &str) -> Result<Vec<Span>, String>;
+
+    /// Limit what is stored to `retention`, dropping the earliest
+    /// stored spans now and as more are appended.
+    fn set_retention(&mut self, retention: Retention) -> Result<(), String>;
+
+    /// Drop stored spans past the limits, such as ones that have aged
+    /// out since they were last checked.
+    fn prune(&mut self) -> Result<(), String>;
+
+    /// Number that changes whenever the stored traces do, so listings
+    /// of them can be cached.
+    fn revision(&self) -> u64;
+}
+
+/// Root span of a stored trace, as listed by [`Store::traces`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoredTrace {
+    pub id: String,
+    pub name: String,
+    /// `service.name` of the root span, if reported.
+    pub service: Option<String>,
+    pub start: chrono::DateTime<chrono::Utc>,
+    pub duration_nanos: i64,
+    /// Number of distinct spans stored for the trace so far.
+    pub span_count: usize,
+}
+
+/// Which traces [`Store::traces`] returns. Unset fields match every
+/// trace.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Filter {
+    /// Exact `service.name` of the root span.
+    pub service: Option<String>,
+    /// Case-insensitive substring of the root span's name.
+    pub name: Option<String>,
+    /// Root span started at or after this time.
+    pub since: Option<chrono::DateTime<chrono::Utc>>,
+    /// Root span started before this time.
+    pub until: Option<chrono::DateTime<chrono::Utc>>,
+    /// Return at most this many traces.
+    pub limit: Option<usize>,
+}
+
+impl Filter {
+    fn matches(&self, trace: &StoredTrace) -> bool {
+        self.service
+            .as_ref()
+            .is_none_or(|service| trace.service.as_ref() == Some(service))
+            && self
+                .name
+                .as_ref()
+                .is_none_or(|name| trace.name.to_lowercase().contains(&name.to_lowercase()))
+            && self.since.is_none_or(|since| trace.start >= since)
+            && self.until.is_none_or(|until| trace.start < until)
+    }
+}
+
+/// Start time and ID of a stored trace, so sets of them are ordered by
+/// when the traces started.
+type StartKey = (chrono::DateTime<chrono::Utc>, String);
+
+/// [`Store`] appending spans, one JSON object per line in the format
+/// read by [`crate::parse_file`], to numbered segment files in a
+/// directory. The index is kept in memory, and rebuilt from the
+/// segments by [`SegmentLog::index`] after [`SegmentLog::open`].
+#[derive(Debug)]
+pub struct SegmentLog {
+    dir: PathBuf,
+    /// Number and length of the segment being appended to.
+    active: (u32, u64),
+    /// Segments written before [`Self::open`] that haven't been read
+    /// into the index yet, oldest first.
+    unindexed: Vec<u32>,
+    /// Size of each indexed segment, by number.
+    segments: BTreeMap<u32, Segment>,
+    /// Limits applied by [`Store::prune`].
+    retention: Retention,
+    /// See [`Store::revision`].
+    revision: u64,
+    /// Where each trace's spans are, by trace ID.
+    entries: HashMap<String, Entry>,
+    /// Every trace whose root span is stored.
+    by_start: BTreeSet<StartKey>,
+    /// [`Self::by_start`] split by the root span's `service.name`.
+    by_service: HashMap<String, BTreeSet<StartKey>>,
+    /// [`Self::by_start`] split by the root span's name, lowercased.
+    by_name: HashMap<String, BTreeSet<StartKey>>,
+}
+
+#[derive(Debug, Default)]
+struct Entry {
+    root: Option<StoredTrace>,
+    /// Segment holding the span of [`Self::root`].
+    root_segment: u32,
+    locations: Vec<Location>,
+    /// IDs of the spans in [`Self::locations`], which may hold the
+    /// same span more than once.
+    span_ids: HashSet<String>,
+}
+
+/// What a segment holds, as counted towards [`SegmentLog::retention`].
+#[derive(Debug, Default, Clone, Copy)]
+struct Segment {
+    bytes: u64,
+    spans: usize,
+    /// When the segment was last written to.
+    written: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// Position of a single span's line.
+#[derive(Debug, Clone)]
+struct Location {
+    span_id: String,
+    segment: u32,
+    offset: u64,
+    len: usize,
+}
+
+impl SegmentLog {
+    /// Open the log in `dir`, creating the directory if needed. Spans
+    /// already in it aren't listed or loadable until [`Self::index`]
+    /// has read them, but new spans can be appended straight away, as
+    /// they go to a new segment.
+    pub fn open(dir: &Path) -> Result<Self, String> {
+        std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        let mut segments = std::fs::read_dir(dir)
+            .map_err(|e| e.to_string())?
+            .filter_map(|entry| segment_number(&entry.ok()?.path()))
+            .collect::<Vec<_>>();
+        segments.sort_unstable();
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            active: (segments.last().map_or(0, |last| last + 1), 0),
+            unindexed: segments,
+            segments: BTreeMap::new(),
+            retention: Retention::default(),
+            revision: 0,
+            entries: HashMap::new(),
+            by_start: BTreeSet::new(),
+            by_service: HashMap::new(),
+            by_name: HashMap::new(),
+        })
+    }
+
+    /// Read the segments written before [`SegmentLog::open`] into the
+    /// index, oldest first. `log` is only locked while each segment's
+    /// spans are added, not while the segment is read, so this can run
+    /// in the background while the log is in use. Lines that can't be
+    /// parsed, such as one cut short by a crash, are skipped.
+    pub fn index(log: &Mutex<Self>) -> Result<(), String> {
+        let (dir, segments) = {
+            let log = log.lock().unwrap();
+            (log.dir.clone(), log.unindexed.clone())
+        };
+        for segment in segments {
+            let path = segment_path(&dir, segment);
+            let contents = std::fs::read(&path).map_err(|e| e.to_string())?;
+            let written = std::fs::metadata(&path)
+                .and_then(|metadata| metadata.modified())
+                .map_err(|e| e.to_string())?;
+            let mut spans = Vec::new();
+            let mut offset = 0;
+            for line in contents.split_inclusive(|&b| b == b'\n') {
+                let len = line.strip_suffix(b"\n").unwrap_or(line).len();
+                let line_offset = offset;
+                offset += line.len() as u64;
+                match serde_json::from_slice::<otel::Span>(line) {
+                    Ok(span) => {
+                        let span = Span::from(span);
+                        let location = Location {
+                            span_id: span.id.clone(),
+                            segment,
+                            offset: line_offset,
+                            len,
+                        };
+                        spans.push((span, location));
+                    }
+                    Err(e) => error!("skipping unreadable span in segment {segment}: {e}"),
+                }
+            }
+
+            let mut log = log.lock().unwrap();
+            for (span, location) in spans {
+                log.index_span(&span, location);
+            }
+            if let Some(indexed) = log.segments.get_mut(&segment) {
+                indexed.written = Some(written.into());
+            }
+            log.unindexed.retain(|&unindexed| unindexed != segment);
+        }
+        log.lock().unwrap().prune()
+    }
+
+    fn segment_path(&self, segment: u32) -> PathBuf {
+        segment_path(&self.dir, segment)
+    }
+
+    fn index_span(&mut self, span: &Span, location: Location) {
+        self.revision += 1;
+        let number = location.segment;
+        let segment = self.segments.entry(number).or_default();
+        segment.bytes += location.len as u64 + 1;
+        segment.spans += 1;
+
+        let entry = self.entries.entry(span.trace_id.clone()).or_default();
+        entry.locations.push(location);
+        entry.span_ids.insert(span.id.clone());
+        if let Some(root) = &mut entry.root {
+            root.span_count = entry.span_ids.len();
+        }
+        if span.parent_id.is_none() {
+            let root = StoredTrace {
+                id: span.trace_id.clone(),
+                name: span.name.clone(),
+                service: span.service_name().map(String::from),
+                start: span.start,
+                duration_nanos: span.duration_nanos,
+                span_count: entry.span_ids.len(),
+            };
+            entry.root_segment = number;
+            if let Some(replaced) = entry.root.replace(root.clone()) {
+                self.unindex_root(&replaced);
+            }
+            let key = (root.start, root.id);
+            if let Some(service) = root.service {
+                self.by_service
+                    .entry(service)
+                    .or_default()
+                    .insert(key.clone());
+            }
+            self.by_name
+                .entry(root.name.to_lowercase())
+                .or_default()
+                .insert(key.clone());
+            self.by_start.insert(key);
+        }
+    }
+
+    /// Bytes after which the segment being appended to is closed: a
+    /// fraction of the byte limit, so pruning never has to delete most
+    /// of what is stored at once.
+    fn segment_bytes(&self) -> u64 {
+        self.retention.max_bytes.map_or(SEGMENT_BYTES, |max_bytes| {
+            (u64::try_from(max_bytes).unwrap_or(u64::MAX) / MIN_SEGMENTS).clamp(1, SEGMENT_BYTES)
+        })
+    }
+
+    /// Forget every span in the segment numbered `number`.
+    fn unindex_segment(&mut self, number: u32) {
+        self.revision += 1;
+        self.segments.remove(&number);
+        let mut unrooted = Vec::new();
+        self.entries.retain(|_, entry| {
+            entry
+                .locations
+                .retain(|location| location.segment != number);
+            entry.span_ids = entry
+                .locations
+                .iter()
+                .map(|location| location.span_id.clone())
+                .collect();
+            if entry.root_segment == number || entry.locations.is_empty() {
+                unrooted.extend(entry.root.take());
+            } else if let Some(root) = &mut entry.root {
+                root.span_count = entry.span_ids.len();
+            }
+            !entry.locations.is_empty()
+        });
+        for root in &unrooted {
+            self.unindex_root(root);
+        }
+    }
+
+    /// Remove `root` from the indexes of root spans.
+    fn unindex_root(&mut self, root: &StoredTrace) {
+        let key = (root.start, root.id.clone());
+        self.by_start.remove(&key);
+        let remove = |index: &mut HashMap<String, BTreeSet<StartKey>>, value: String| {
+            if let Some(keys) = index.get_mut(&value) {
+                keys.remove(&key);
+                if keys.is_empty() {
+                    index.remove(&value);
+                }
+            }
+        };
+        if let Some(service) = &root.service {
+            remove(&mut self.by_service, service.clone());
+        }
+        remove(&mut self.by_name, root.name.to_lowercase());
+    }
+}
+
+impl Store for SegmentLog {
+    fn append(&mut self, spans: &[Span]) -> Result<(), String> {
+        if spans.is_empty() {
+            return Ok(());
+        }
+        if self.active.1 >= self.segment_bytes() {
+            self.active = (self.active.0 + 1, 0);
+            self.prune()?;
+        }
+        let (segment, mut offset) = self.active;
+        let mut contents = String::new();
+        let mut locations = Vec::with_capacity(spans.len());
+        for span in spans {
+            let len = crate::push_span_line(&mut contents, span)?;
+            locations.push(Location {
+                span_id: span.id.clone(),
+                segment,
+                offset,
+                len,
+            });
+            offset += len as u64 + 1;
+        }
+        let written = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.segment_path(segment))
+            .and_then(|mut f| f.write_all(contents.as_bytes()));
+        if let Err(e) = written {
+            // part of a line may have been written, which would throw
+            // off the offsets of the lines after it
+            self.active = (segment + 1, 0);
+            return Err(e.to_string());
+        }
+
+        self.active.1 = offset;
+        for (span, location) in spans.iter().zip(locations) {
+            self.index_span(span, location);
+        }
+        if let Some(indexed) = self.segments.get_mut(&segment) {
+            indexed.written = Some(chrono::Utc::now());
+        }
+        Ok(())
+    }
+
+    fn traces(&self, filter: &Filter) -> Vec<StoredTrace> {
+        if let (Some(since), Some(until)) = (filter.since, filter.until) {
+            if since >= until {
+                return Vec::new();
+            }
+        }
+        let range = (
+            filter.since.map_or(Bound::Unbounded, |since| {
+                Bound::Included((since, String::new()))
+            }),
+            filter.until.map_or(Bound::Unbounded, |until| {
+                Bound::Excluded((until, String::new()))
+            }),
+        );
+        // narrowed down by the most selective index the filter allows,
+        // then checked against the whole filter
+        let indexes = match (&filter.service, &filter.name) {
+            (Some(service), _) => self.by_service.get(service).into_iter().collect(),
+            (None, Some(name)) => {
+                let name = name.to_lowercase();
+                self.by_name
+                    .iter()
+                    .filter(|(key, _)| key.contains(&name))
+                    .map(|(_, keys)| keys)
+                    .collect()
+            }
+            (None, None) => vec![&self.by_start],
+        };
+        let limit = filter.limit.unwrap_or(usize::MAX);
+        let root = |(_, id): &StartKey| self.entries.get(id)?.root.as_ref();
+        let mut keys = indexes
+            .into_iter()
+            .flat_map(|keys| {
+                keys.range(range.clone())
+                    .rev()
+                    .filter(|key| root(key).is_some_and(|trace| filter.matches(trace)))
+                    .take(limit)
+            })
+            .collect::<Vec<_>>();
+        keys.sort_unstable_by(|l, r| r.cmp(l));
+        keys.into_iter()
+            .take(limit)
+            .filter_map(root)
+            .cloned()
+            .collect()
+    }
+
+    fn load(&self, trace_id: &str) -> Result<Vec<Span>, String> {
+        let Some(entry) = self.entries.get(trace_id) else {
+            return Ok(Vec::new());
+        };
+        let mut files = HashMap::new();
+        let mut seen = HashSet::new();
+        let mut spans = Vec::with_capacity(entry.span_ids.len());
+        for location in &entry.locations {
+            // the same span may have been imported more than once
+            if !seen.insert(&location.span_id) {
+                continue;
+            }
+            let file = match files.entry(location.segment) {
+                std::collections::hash_map::Entry::Occupied(file) => file.into_mut(),
+                std::collections::hash_map::Entry::Vacant(slot) => slot.insert(
+                    File::open(self.segment_path(location.segment)).map_err(|e| e.to_string())?,
+                ),
+            };
+            let mut line = vec![0; location.len];
+            file.seek(SeekFrom::Start(location.offset))
+                .and_then(|_| file.read_exact(&mut line))
+                .map_err(|e| e.to_string())?;
+            spans.push(Span::from(
+                serde_json::from_slice::<otel::Span>(&line).map_err(|e| e.to_string())?,
+            ));
+        }
+        Ok(spans)
+    }
+
+    fn set_retention(&mut self, retention: Retention) -> Result<(), String> {
+        self.retention = retention;
+        self.prune()
+    }
+
+    /// Delete the earliest segments while they hold more spans or
+    /// bytes than [`Self::retention`] allows, or were last written to
+    /// longer ago than its age limit. Appending moves on to a new
+    /// segment if the one being appended to is deleted. Nothing is
+    /// deleted until [`Self::index`] has read every segment, so the
+    /// earliest go first. The trace limit isn't applied, as a trace's
+    /// spans may be spread across segments.
+    fn prune(&mut self) -> Result<(), String> {
+        if !self.unindexed.is_empty() {
+            return Ok(());
+        }
+        let now = chrono::Utc::now();
+        let mut usage = Usage {
+            traces: 0,
+            spans: self.segments.values().map(|segment| segment.spans).sum(),
+            bytes: self
+                .segments
+                .values()
+                .map(|segment| usize::try_from(segment.bytes).unwrap_or(usize::MAX))
+                .sum(),
+        };
+        while let Some((&number, &segment)) = self.segments.iter().next() {
+            let expired = self.retention.max_age.is_some_and(|max_age| {
+                segment
+                    .written
+                    .is_none_or(|written| now - written > max_age)
+            });
+            if !(expired || self.retention.is_exceeded(&usage)) {
+                break;
+            }
+            match std::fs::remove_file(self.segment_path(number)) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.to_string()),
+                _ => {}
+            }
+            if number == self.active.0 {
+                self.active = (number + 1, 0);
+            }
+            self.unindex_segment(number);
+            usage.spans -= segment.spans;
+            usage.bytes -= usize::try_from(segment.bytes).unwrap_or(usize::MAX);
+        }
+        Ok(())
+    }
+
+    fn revision(&self) -> u64 {
+        self.revision
+    }
+}
+
+fn segment_path(dir: &Path, segment: u32) -> PathBuf {
+    dir.join(format!("segment-{segment:06}.jsonl"))
+}
+
+/// Number of the segment at `path`, if it is one.
+fn segment_number(path: &Path) -> Option<u32> {
+    path.file_name()?
+        .to_str()?
+        .strip_prefix("segment-")?
+        .strip_suffix(".jsonl")?
+        .parse()
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        path::{Path, PathBuf},
+        sync::Mutex,
+    };
+
+    use super::{Filter, SegmentLog, Store};
+    use crate::{retention::Retention, test_util, Span};
+
+    /// Empty directory for a test's log.
+    fn dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("store_{}_{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    /// Open the log in `dir` and index what is already in it.
+    fn reopen(dir: &Path) -> Result<SegmentLog, String> {
+        let log = Mutex::new(SegmentLog::open(dir)?);
+        SegmentLog::index(&log)?;
+        log.into_inner().map_err(|e| e.to_string())
+    }
+
+    /// Span of trace `trace_id` from service "`name`_service",
+    /// starting `minutes` after the Unix epoch.
+    fn span(trace_id: &str, id: &str, parent_id: Option<&str>, name: &str, minutes: i64) -> Span {
+        let offset = chrono::Duration::minutes(minutes)
+            .num_nanoseconds()
+            .unwrap_or_default();
+        let span = test_util::span(id, name, parent_id, offset, 1_000);
+        Span {
+            trace_id: trace_id.to_string(),
+            ..test_util::with_service(span, &format!("{name}_service"))
+        }
+    }
+
+    #[test]
+    fn indexes_and_loads_traces_across_reopening() -> Result<(), String> {
+        let dir = dir("reopen");
+        let mut log = SegmentLog::open(&dir)?;
+        log.append(&[
+            span("one", "one_root", None, "checkout", 1),
+            span("two", "two_root", None, "search", 2),
+        ])?;
+        let revision = log.revision();
+        log.append(&[span("one", "one_child", Some("one_root"), "db", 1)])?;
+        assert_ne!(log.revision(), revision);
+        // imported again, which shouldn't count twice
+        log.append(&[span("one", "one_child", Some("one_root"), "db", 1)])?;
+
+        let ids = |log: &SegmentLog, filter: &Filter| {
+            log.traces(filter)
+                .into_iter()
+                .map(|trace| trace.id)
+                .collect::<Vec<_>>()
+        };
+        let log = SegmentLog::open(&dir)?;
+        assert!(log.traces(&Filter::default()).is_empty());
+        let log = reopen(&dir)?;
+        assert_eq!(ids(&log, &Filter::default()), vec!["two", "one"]);
+        assert_eq!(log.traces(&Filter::default())[1].span_count, 2);
+        let filter = Filter {
+            service: Some("checkout_service".to_string()),
+            ..Filter::default()
+        };
+        assert_eq!(ids(&log, &filter), vec!["one"]);
+        let filter = Filter {
+            name: Some("SEAR".to_string()),
+            ..Filter::default()
+        };
+        assert_eq!(ids(&log, &filter), vec!["two"]);
+        let filter = Filter {
+            until: Some(chrono::DateTime::<chrono::Utc>::UNIX_EPOCH + chrono::Duration::minutes(2)),
+            ..Filter::default()
+        };
+        assert_eq!(ids(&log, &filter), vec!["one"]);
+        let filter = Filter {
+            since: Some(chrono::DateTime::<chrono::Utc>::UNIX_EPOCH + chrono::Duration::minutes(2)),
+            ..Filter::default()
+        };
+        assert_eq!(ids(&log, &filter), vec!["two"]);
+        let filter = Filter {
+            service: Some("checkout_service".to_string()),
+            name: Some("search".to_string()),
+            ..Filter::default()
+        };
+        assert!(ids(&log, &filter).is_empty());
+        let filter = Filter {
+            limit: Some(1),
+            ..Filter::default()
+        };
+        assert_eq!(ids(&log, &filter), vec!["two"]);
+
+        let spans = log.load("one")?;
+        let span_ids = spans
+            .iter()
+            .map(|span| span.id.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(span_ids, vec!["one_root", "one_child"]);
+        assert_eq!(spans[1].parent_id.as_deref(), Some("one_root"));
+        assert!(log.load("missing")?.is_empty());
+
+        let _ = std::fs::remove_dir_all(&dir);
+        Ok(())
+    }
+
+    #[test]
+    fn prunes_earliest_segments() -> Result<(), String> {
+        let dir = dir("prune");
+        // every reopening starts a new segment
+        for (n, trace_id) in ["one", "two", "three"].into_iter().enumerate() {
+            let mut log = reopen(&dir)?;
+            let root = format!("{trace_id}_root");
+            log.append(&[
+                span(trace_id, &root, None, "checkout", 1),
+                span(trace_id, &format!("{trace_id}_child"), Some(&root), "db", 1),
+            ])?;
+            assert_eq!(log.segments.len(), n + 1);
+        }
+        let ids = |log: &SegmentLog| {
+            log.traces(&Filter::default())
+                .into_iter()
+                .map(|trace| trace.id)
+                .collect::<Vec<_>>()
+        };
+
+        let mut log = reopen(&dir)?;
+        log.set_retention(Retention {
+            max_spans: Some(5),
+            ..Retention::default()
+        })?;
+        assert_eq!(ids(&log), vec!["two", "three"]);
+        assert!(!log.segment_path(0).exists());
+        assert!(log.load("one")?.is_empty());
+
+        // the spans started long ago, but only when the segments were
+        // written counts
+        log.append(&[span("four", "four_root", None, "search", 2)])?;
+        let max_age = chrono::Duration::days(1);
+        log.set_retention(Retention {
+            max_age: Some(max_age),
+            ..Retention::default()
+        })?;
+        assert_eq!(ids(&log), vec!["four", "two", "three"]);
+        let aged = chrono::Utc::now() - max_age * 2;
+        for (_, segment) in log.segments.range_mut(..log.active.0) {
+            segment.written = Some(aged);
+        }
+        log.prune()?;
+        assert_eq!(ids(&log), vec!["four"]);
+
+        // the segment being appended to goes too, and appending moves
+        // on to the next
+        log.set_retention(Retention {
+            max_spans: Some(0),
+            ..Retention::default()
+        })?;
+        assert!(ids(&log).is_empty());
+        log.set_retention(Retention::default())?;
+        log.append(&[span("five", "five_root", None, "search", 3)])?;
+        assert_eq!(log.active.0, 4);
+        assert_eq!(ids(&reopen(&dir)?), vec!["five"]);
+
+        let _ = std::fs::remove_dir_all(&dir);
+        Ok(())
+    }
+
+    #[test]
+    fn splits_byte_limit_across_segments() -> Result<(), String> {
+        let dir = dir("split");
+        let root = |n: usize| span(&format!("trace{n}"), &format!("root{n}"), None, "search", 1);
+        let mut log = SegmentLog::open(&dir)?;
+        log.append(&[root(0)])?;
+        let line = usize::try_from(log.active.1).map_err(|e| e.to_string())?;
+        log.set_retention(Retention {
+            max_bytes: Some(line * 4),
+            ..Retention::default()
+        })?;
+        // segments close at half a line, so every append starts one
+        for n in 1..8 {
+            log.append(&[root(n)])?;
+        }
+        log.prune()?;
+        assert_eq!(log.segments.len(), 4);
+        let ids = log
+            .traces(&Filter::default())
+            .into_iter()
+            .map(|trace| trace.id)
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec!["trace7", "trace6", "trace5", "trace4"]);
+
+        let _ = std::fs::remove_dir_all(&dir);
+        Ok(())
+    }
+
+    #[test]
+    fn skips_unreadable_lines() -> Result<(), String> {
+        let dir = dir("unreadable");
+        let mut log = SegmentLog::open(&dir)?;
+        log.append(&[span("one", "one_root", None, "checkout", 1)])?;
+        let segment = log.segment_path(0);
+        let mut contents = std::fs::read(&segment).map_err(|e| e.to_string())?;
+        // a multibyte character cut short, then a line cut short
+        contents.extend_from_slice(b"{\"Name\":\"\xE2\x82\"}\n");
+        contents.extend_from_slice(br#"{"Name":"cut"#);
+        std::fs::write(&segment, contents).map_err(|e| e.to_string())?;
+
+        let mut log = reopen(&dir)?;
+        log.append(&[span("two", "two_root", None, "search", 2)])?;
+        let log = reopen(&dir)?;
+        assert_eq!(log.traces(&Filter::default()).len(), 2);
+        assert_eq!(log.load("one")?.len(), 1);
+        assert_eq!(log.load("two")?.len(), 1);
+
+        let _ = std::fs::remove_dir_all(&dir);
+        Ok(())
+    }
+}
@@ -0,0 +1,24 @@
+//! Fixtures shared by the unit tests.
+
+use crate::Span;
+
+/// Span of trace "trace", starting `offset` nanoseconds after the Unix
+/// epoch and lasting `duration` nanoseconds.
+pub(crate) fn span(id: &str, name: &str, parent: Option<&str>, offset: i64, duration: i64) -> Span {
+    Span {
+        trace_id: "trace".to_string(),
+        id: id.to_string(),
+        name: name.to_string(),
+        parent_id: parent.map(String::from),
+        start: chrono::DateTime::<chrono::Utc>::UNIX_EPOCH + chrono::Duration::nanoseconds(offset),
+        duration_nanos: duration,
+        ..Span::default()
+    }
+}
+
+/// `span` as reported by `service`.
+pub(crate) fn with_service(mut span: Span, service: &str) -> Span {
+    span.metadata
+        .insert("service.name".to_string(), service.to_string());
+    span
+}